@@ -1,7 +1,14 @@
-use clap::Parser;
-use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb};
+use crate::utils::concurrency::resolve_thread_count;
+use crate::utils::images::open_image;
+use ab_glyph::{FontRef, PxScale};
+use clap::ValueEnum;
+use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb, Rgba};
+use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut, text_size};
+use imageproc::geometric_transformations::{rotate_about_center, warp, Interpolation, Projection};
+use imageproc::rect::Rect;
 use indicatif::{ProgressBar, ProgressStyle};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
 use std::fs;
 use std::io;
@@ -9,32 +16,228 @@ use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Augmentation parameters
-#[derive(Parser, Debug)]
-#[command(about = "Generate augmented versions of TCG card images")]
-pub struct AugmentationArgs {
+#[derive(Debug, Clone)]
+pub struct AugmentOptions {
     /// Path to the dataset directory (should have train/, test/, validation/ subdirs)
-    #[arg(short, long)]
     pub path: String,
 
     /// Number of augmented versions to generate per image
-    #[arg(short, long, default_value_t = 5)]
     pub amount: u32,
 
     /// Verify image integrity after augmentation
-    #[arg(long, default_value_t = false)]
     pub verify: bool,
+
+    /// File name template for augmented images ({index}, {index:N}, {card}, {orig})
+    pub naming_template: String,
+
+    /// Image format for augmented outputs
+    pub output_format: AugmentOutputFormat,
+
+    /// Augmentations to choose from (empty means all)
+    pub augmentations: Vec<AugmentationType>,
+
+    /// Color used to fill areas exposed by perspective and shear
+    pub fill_color: Rgb<u8>,
+
+    /// Intensity ranges for each augmentation
+    pub intensities: AugmentationIntensities,
+
+    /// Number of worker threads (0 means all cores)
+    pub threads: usize,
+
+    /// Draw the applied augmentations onto each augmented image
+    pub label: Option<LabelStyle>,
+
+    /// Augment card directories again even if their marker says they are done
+    pub force: bool,
 }
 
-/// Types of augmentations to apply
+/// Corner of the image a label is drawn in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LabelPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Where and in which color augmentation labels are drawn
 #[derive(Debug, Clone, Copy)]
+pub struct LabelStyle {
+    pub position: LabelPosition,
+    pub color: Rgb<u8>,
+}
+
+/// Font bundled for labels, so output does not depend on system fonts
+///
+/// DejaVu Sans Mono subset to printable ASCII, which covers every label.
+const LABEL_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono-ASCII.ttf");
+
+/// Label style together with the loaded font
+struct Label {
+    style: LabelStyle,
+    font: FontRef<'static>,
+}
+
+/// Image format used when saving augmented images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AugmentOutputFormat {
+    /// Always save as JPEG (lossy)
+    Jpeg,
+    /// Always save as PNG (lossless)
+    Png,
+    /// Keep the source image's format (JPEG or PNG), falling back to JPEG
+    Preserve,
+}
+
+impl AugmentOutputFormat {
+    /// Resolve the concrete format and file extension for a source image
+    fn resolve(self, source: &Path) -> (ImageFormat, &'static str) {
+        let format = match self {
+            Self::Jpeg => ImageFormat::Jpeg,
+            Self::Png => ImageFormat::Png,
+            Self::Preserve => match ImageFormat::from_path(source) {
+                Ok(ImageFormat::Png) => ImageFormat::Png,
+                _ => ImageFormat::Jpeg,
+            },
+        };
+
+        match format {
+            ImageFormat::Png => (format, "png"),
+            _ => (ImageFormat::Jpeg, "jpg"),
+        }
+    }
+}
+
+/// Default augmented file name: the next free index, zero-padded to four digits
+pub const DEFAULT_NAMING_TEMPLATE: &str = "{index:04}";
+
+/// A placeholder or literal piece of a naming template
+#[derive(Debug, Clone)]
+enum TemplatePart {
+    Literal(String),
+    Index(usize),
+    Card,
+    Orig,
+}
+
+/// Validated file name template for augmented images
+#[derive(Debug, Clone)]
+pub struct NamingTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl NamingTemplate {
+    /// Parse and validate a naming template
+    pub fn parse(template: &str) -> Result<Self, String> {
+        if template.contains('/') || template.contains('\\') {
+            return Err("Naming template must not contain path separators".to_string());
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(TemplatePart::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed placeholder in naming template: {}", template))?
+                + start;
+            let placeholder = &rest[start + 1..end];
+            parts.push(match placeholder {
+                "index" => TemplatePart::Index(0),
+                "card" => TemplatePart::Card,
+                "orig" => TemplatePart::Orig,
+                _ => match placeholder.strip_prefix("index:") {
+                    Some(width) => TemplatePart::Index(width.parse().map_err(|_| {
+                        format!("Invalid index width in naming template: {}", placeholder)
+                    })?),
+                    None => {
+                        return Err(format!(
+                            "Unknown placeholder in naming template: {{{}}}",
+                            placeholder
+                        ))
+                    }
+                },
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest.to_string()));
+        }
+
+        // Without an index every augmented image would get the same name
+        if !parts
+            .iter()
+            .any(|part| matches!(part, TemplatePart::Index(_)))
+        {
+            return Err("Naming template must contain an {index} placeholder".to_string());
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Render a file name (without extension) for an augmented image
+    fn render(&self, index: u32, card: &str, orig: &str) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Literal(text) => text.clone(),
+                TemplatePart::Index(width) => format!("{:0width$}", index, width = width),
+                TemplatePart::Card => card.to_string(),
+                TemplatePart::Orig => orig.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Settings shared by every card directory during augmentation
+struct AugmentationConfig {
+    amount: u32,
+    naming: NamingTemplate,
+    output_format: AugmentOutputFormat,
+    augmentations: Vec<AugmentationType>,
+    fill_color: Rgb<u8>,
+    intensities: AugmentationIntensities,
+    threads: usize,
+    label: Option<Label>,
+    force: bool,
+}
+
+/// File written into a card directory once all of its images are augmented,
+/// holding the number of versions generated per image
+const AUGMENTED_MARKER: &str = ".augmented";
+
+/// Whether a card directory was already augmented with `amount` versions per image
+fn is_augmented(card_dir: &Path, amount: u32) -> bool {
+    fs::read_to_string(card_dir.join(AUGMENTED_MARKER))
+        .ok()
+        .and_then(|content| content.trim().parse::<u32>().ok())
+        == Some(amount)
+}
+
+/// Types of augmentations to apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum AugmentationType {
+    /// Rotate the image
     Rotation,
+    /// Adjust brightness
     Brightness,
+    /// Adjust contrast
     Contrast,
+    /// Adjust saturation
     Saturation,
+    /// Add random noise
     Noise,
+    /// Gaussian blur
     Blur,
+    /// Flip horizontally or vertically
     Flip,
+    /// Random perspective distortion, as if photographed at an angle
+    Perspective,
+    /// Random horizontal or vertical shear
+    Shear,
 }
 
 impl AugmentationType {
@@ -47,8 +250,151 @@ impl AugmentationType {
             Self::Noise,
             Self::Blur,
             Self::Flip,
+            Self::Perspective,
+            Self::Shear,
         ]
     }
+
+    /// Name of this augmentation as given on the command line
+    fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_else(|| format!("{:?}", self).to_lowercase())
+    }
+}
+
+/// Parse a fill color given as `r,g,b` or `#rrggbb`
+pub fn parse_fill_color(value: &str) -> Result<Rgb<u8>, String> {
+    let invalid = || format!("Invalid color '{}', expected r,g,b or #rrggbb", value);
+
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        return Ok(Rgb([channel(0)?, channel(2)?, channel(4)?]));
+    }
+
+    let channels: Vec<u8> = value
+        .split(',')
+        .map(|part| part.trim().parse::<u8>().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    match channels.as_slice() {
+        [r, g, b] => Ok(Rgb([*r, *g, *b])),
+        _ => Err(invalid()),
+    }
+}
+
+/// Inclusive range an augmentation's intensity is drawn from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntensityRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl IntensityRange {
+    const fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> f32 {
+        if self.min >= self.max {
+            self.min
+        } else {
+            rng.random_range(self.min..=self.max)
+        }
+    }
+}
+
+/// Intensity ranges for every augmentation that has one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AugmentationIntensities {
+    /// Rotation angle in degrees
+    pub rotation: IntensityRange,
+    /// Brightness offset added to each channel
+    pub brightness: IntensityRange,
+    /// Contrast factor around mid-gray
+    pub contrast: IntensityRange,
+    /// Saturation factor (0 is grayscale)
+    pub saturation: IntensityRange,
+    /// Maximum per-channel noise offset
+    pub noise: IntensityRange,
+    /// Gaussian blur sigma
+    pub blur: IntensityRange,
+    /// How far each corner moves inward, as a fraction of the image size
+    pub perspective: IntensityRange,
+    /// Shear factor
+    pub shear: IntensityRange,
+}
+
+impl Default for AugmentationIntensities {
+    fn default() -> Self {
+        Self {
+            rotation: IntensityRange::new(-15.0, 15.0),
+            brightness: IntensityRange::new(-30.0, 30.0),
+            contrast: IntensityRange::new(0.7, 1.3),
+            saturation: IntensityRange::new(0.5, 1.5),
+            noise: IntensityRange::new(5.0, 25.0),
+            blur: IntensityRange::new(0.5, 2.0),
+            perspective: IntensityRange::new(0.0, 0.1),
+            shear: IntensityRange::new(-0.2, 0.2),
+        }
+    }
+}
+
+impl AugmentationIntensities {
+    /// Override the range for one augmentation
+    pub fn set(&mut self, aug_type: AugmentationType, range: IntensityRange) {
+        let slot = match aug_type {
+            AugmentationType::Rotation => &mut self.rotation,
+            AugmentationType::Brightness => &mut self.brightness,
+            AugmentationType::Contrast => &mut self.contrast,
+            AugmentationType::Saturation => &mut self.saturation,
+            AugmentationType::Noise => &mut self.noise,
+            AugmentationType::Blur => &mut self.blur,
+            AugmentationType::Perspective => &mut self.perspective,
+            AugmentationType::Shear => &mut self.shear,
+            AugmentationType::Flip => return,
+        };
+        *slot = range;
+    }
+}
+
+/// Parse an intensity override given as `<type>=<min>:<max>`, e.g. `rotation=-5:5`
+pub fn parse_intensity(value: &str) -> Result<(AugmentationType, IntensityRange), String> {
+    let invalid = || format!("Invalid intensity '{}', expected <type>=<min>:<max>", value);
+
+    let (name, range) = value.split_once('=').ok_or_else(invalid)?;
+    let aug_type = AugmentationType::from_str(name.trim(), true)?;
+    let (min, max) = range.split_once(':').ok_or_else(invalid)?;
+    let min: f32 = min.trim().parse().map_err(|_| invalid())?;
+    let max: f32 = max.trim().parse().map_err(|_| invalid())?;
+
+    if !min.is_finite() || !max.is_finite() || min > max {
+        return Err(format!(
+            "Invalid intensity '{}', min must not exceed max",
+            value
+        ));
+    }
+
+    let (lower, upper) = match aug_type {
+        AugmentationType::Rotation => (-180.0, 180.0),
+        AugmentationType::Brightness => (-255.0, 255.0),
+        AugmentationType::Contrast | AugmentationType::Saturation => (0.0, 10.0),
+        AugmentationType::Noise => (0.0, 255.0),
+        AugmentationType::Blur => (0.0, 50.0),
+        AugmentationType::Perspective => (0.0, 0.45),
+        AugmentationType::Shear => (-1.0, 1.0),
+        AugmentationType::Flip => return Err("Flip has no intensity to configure".to_string()),
+    };
+    if min < lower || max > upper {
+        return Err(format!(
+            "Invalid intensity '{}', {} range must be within {}:{}",
+            value, name, lower, upper
+        ));
+    }
+
+    Ok((aug_type, IntensityRange::new(min, max)))
 }
 
 /// Statistics for augmentation process
@@ -57,15 +403,26 @@ struct AugmentationStats {
     total_original_images: usize,
     total_augmented_images: usize,
     total_cards: usize,
+    skipped_cards: usize,
     train_images: usize,
     test_images: usize,
     validation_images: usize,
     corrupted_images: usize,
     verified_images: usize,
+    failed_directories: Vec<PathBuf>,
+}
+
+/// Result of augmenting a single subset directory
+struct SubsetResult {
+    card_count: usize,
+    skipped_count: usize,
+    total_images: usize,
+    original_images: usize,
+    failed_directories: Vec<PathBuf>,
 }
 
 /// Apply augmentations to the dataset
-pub async fn augment_dataset(args: AugmentationArgs) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn augment_dataset(args: AugmentOptions) -> Result<(), Box<dyn std::error::Error>> {
     let base_dir = Path::new(&args.path);
 
     // Check if the train directory exists
@@ -79,18 +436,42 @@ pub async fn augment_dataset(args: AugmentationArgs) -> Result<(), Box<dyn std::
     println!("Base directory: {}", args.path);
     println!("Augmentations per image: {}", args.amount);
 
+    let config = AugmentationConfig {
+        amount: args.amount,
+        naming: NamingTemplate::parse(&args.naming_template)?,
+        output_format: args.output_format,
+        augmentations: if args.augmentations.is_empty() {
+            AugmentationType::all()
+        } else {
+            args.augmentations.clone()
+        },
+        fill_color: args.fill_color,
+        intensities: args.intensities,
+        threads: resolve_thread_count(args.threads),
+        label: match args.label {
+            Some(style) => Some(Label {
+                style,
+                font: FontRef::try_from_slice(LABEL_FONT)?,
+            }),
+            None => None,
+        },
+        force: args.force,
+    };
+
     let mut stats = AugmentationStats::default();
 
     // Process train subset only
-    let train_stats = process_subset(&train_dir, args.amount, "Training").await?;
+    let train_stats = process_subset(&train_dir, &config, "Training").await?;
 
     // Set statistics
-    stats.total_cards = train_stats.0;
-    stats.train_images = train_stats.1;
+    stats.total_cards = train_stats.card_count;
+    stats.skipped_cards = train_stats.skipped_count;
+    stats.train_images = train_stats.total_images;
     stats.test_images = 0;
     stats.validation_images = 0;
-    stats.total_original_images = train_stats.2;
-    stats.total_augmented_images = train_stats.1;
+    stats.total_original_images = train_stats.original_images;
+    stats.total_augmented_images = train_stats.total_images;
+    stats.failed_directories = train_stats.failed_directories;
 
     // Verify images if requested
     if args.verify {
@@ -110,17 +491,25 @@ pub async fn augment_dataset(args: AugmentationArgs) -> Result<(), Box<dyn std::
         );
     }
 
+    if !stats.failed_directories.is_empty() {
+        return Err(format!(
+            "{} card directories failed to augment",
+            stats.failed_directories.len()
+        )
+        .into());
+    }
+
     println!("Augmentation process completed successfully!");
     Ok(())
 }
 
 /// Process a subset directory (train, test, or validation)
-/// Returns (card_count, total_augmented_images, original_images_count)
 async fn process_subset(
     subset_dir: &Path,
-    amount: u32,
+    config: &AugmentationConfig,
     subset_name: &str,
-) -> Result<(usize, usize, usize), Box<dyn std::error::Error>> {
+) -> Result<SubsetResult, Box<dyn std::error::Error>> {
+    let amount = config.amount;
     println!("\nProcessing {} set...", subset_name);
 
     // Get all card directories
@@ -138,11 +527,34 @@ async fn process_subset(
 
     if card_dirs.is_empty() {
         println!("No card directories found in {}", subset_dir.display());
-        return Ok((0, 0, 0));
+        return Ok(SubsetResult {
+            card_count: 0,
+            skipped_count: 0,
+            total_images: 0,
+            original_images: 0,
+            failed_directories: Vec::new(),
+        });
     }
 
     println!("Found {} card directories", card_dirs.len());
 
+    // Directories finished by an earlier, interrupted run would otherwise have
+    // their augmented images augmented again
+    let (done, card_dirs): (Vec<_>, Vec<_>) = if config.force {
+        (Vec::new(), card_dirs)
+    } else {
+        card_dirs
+            .into_iter()
+            .partition(|card_dir| is_augmented(card_dir, amount))
+    };
+    if !done.is_empty() {
+        println!(
+            "Skipping {} card directories already augmented with {} per image (use --force to redo them)",
+            done.len(),
+            amount
+        );
+    }
+
     // Count total images for progress bar
     let total_original_images = count_images(&card_dirs)?;
     let total_augmentations = total_original_images * amount as usize;
@@ -159,16 +571,28 @@ async fn process_subset(
     progress_bar.set_message(format!("Augmenting {} images", subset_name.to_lowercase()));
 
     let processed_count = AtomicUsize::new(0);
-
-    // Process card directories in parallel
-    card_dirs.par_iter().for_each(|card_dir| {
-        if let Err(e) = process_card_directory(card_dir, amount, &progress_bar, &processed_count) {
-            eprintln!(
-                "Error processing card directory {}: {}",
-                card_dir.display(),
-                e
-            );
-        }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.threads)
+        .build()?;
+
+    // Process card directories in parallel, keeping track of the ones that fail
+    let failed_directories: Vec<PathBuf> = pool.install(|| {
+        card_dirs
+            .par_iter()
+            .filter_map(|card_dir| {
+                match process_card_directory(card_dir, config, &progress_bar, &processed_count) {
+                    Ok(()) => None,
+                    Err(e) => {
+                        eprintln!(
+                            "Error processing card directory {}: {}",
+                            card_dir.display(),
+                            e
+                        );
+                        Some(card_dir.clone())
+                    }
+                }
+            })
+            .collect()
     });
 
     progress_bar.finish_with_message(format!(
@@ -176,17 +600,25 @@ async fn process_subset(
         subset_name.to_lowercase()
     ));
 
-    let final_total_images = count_images(&card_dirs)?;
-    Ok((card_dirs.len(), final_total_images, total_original_images))
+    // Derive the final count instead of walking every directory a second time
+    let final_total_images = total_original_images + total_augmentations;
+    Ok(SubsetResult {
+        card_count: card_dirs.len(),
+        skipped_count: done.len(),
+        total_images: final_total_images,
+        original_images: total_original_images,
+        failed_directories,
+    })
 }
 
 /// Process a single card directory
 fn process_card_directory(
     card_dir: &Path,
-    amount: u32,
+    config: &AugmentationConfig,
     progress_bar: &ProgressBar,
     processed_count: &AtomicUsize,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let amount = config.amount;
     // Find all image files in the card directory
     let image_files: Vec<_> = fs::read_dir(card_dir)?
         .filter_map(|entry| {
@@ -202,22 +634,65 @@ fn process_card_directory(
 
     // Process each image file
     for image_path in image_files {
-        generate_augmentations(&image_path, amount)?;
+        generate_augmentations(&image_path, config)?;
 
         // Update progress
         let current = processed_count.fetch_add(amount as usize, Ordering::Relaxed);
         progress_bar.set_position((current + amount as usize) as u64);
     }
 
+    fs::write(card_dir.join(AUGMENTED_MARKER), format!("{}\n", amount))?;
     Ok(())
 }
 
+/// Seed for preview outputs so repeated previews of an image are identical
+const PREVIEW_SEED: u64 = 42;
+
+/// Write one example of each augmentation applied to a single image
+///
+/// Outputs are named after the augmentation (e.g. `rotation.jpg`) so each
+/// transform can be inspected on its own before augmenting a full dataset.
+pub fn preview_augmentations(
+    image_path: &Path,
+    output_dir: &Path,
+    augmentations: &[AugmentationType],
+    output_format: AugmentOutputFormat,
+    fill_color: Rgb<u8>,
+    intensities: &AugmentationIntensities,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let img = open_image(image_path)?;
+    fs::create_dir_all(output_dir)?;
+
+    let augmentations = if augmentations.is_empty() {
+        AugmentationType::all()
+    } else {
+        augmentations.to_vec()
+    };
+    let (format, extension) = output_format.resolve(image_path);
+
+    let mut outputs = Vec::new();
+    for aug_type in augmentations {
+        let mut rng = StdRng::seed_from_u64(PREVIEW_SEED);
+        let augmented_img = apply_augmentation(&img, aug_type, &mut rng, intensities, fill_color);
+        let augmented_img = match format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(augmented_img.to_rgb8()),
+            _ => augmented_img,
+        };
+
+        let output_path = output_dir.join(format!("{}.{}", aug_type.name(), extension));
+        augmented_img.save_with_format(&output_path, format)?;
+        outputs.push(output_path);
+    }
+
+    Ok(outputs)
+}
+
 /// Generate augmented versions of a single image
 fn generate_augmentations(
     image_path: &Path,
-    amount: u32,
+    config: &AugmentationConfig,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let img = image::open(image_path)?;
+    let img = open_image(image_path)?;
     let mut rng = rand::rng();
 
     let parent_dir = image_path.parent().ok_or("Invalid parent directory")?;
@@ -239,23 +714,59 @@ fn generate_augmentations(
         }
     }
 
-    // Generate augmented versions
-    for i in 1..=amount {
-        let augmented_img = apply_random_augmentations(&img, &mut rng);
+    let card_name = parent_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let orig_name = image_path
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
 
-        let output_filename = format!("{:04}.jpg", max_existing + i);
-        let output_path = parent_dir.join(output_filename);
+    let (output_format, extension) = config.output_format.resolve(image_path);
 
-        augmented_img.save_with_format(&output_path, ImageFormat::Jpeg)?;
+    // Generate augmented versions
+    let mut index = max_existing;
+    for _ in 0..config.amount {
+        let (augmented_img, applied) = apply_random_augmentations(&img, &mut rng, config);
+        let augmented_img = match &config.label {
+            Some(label) => draw_label(&augmented_img, &applied, label),
+            None => augmented_img,
+        };
+
+        // Skip any index whose rendered name is already taken
+        let output_path = loop {
+            index += 1;
+            let output_filename = format!(
+                "{}.{}",
+                config.naming.render(index, &card_name, &orig_name),
+                extension
+            );
+            let candidate = parent_dir.join(output_filename);
+            if !candidate.exists() {
+                break candidate;
+            }
+        };
+
+        // JPEG has no alpha channel, so flatten to RGB first
+        let augmented_img = match output_format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(augmented_img.to_rgb8()),
+            _ => augmented_img,
+        };
+        augmented_img.save_with_format(&output_path, output_format)?;
     }
 
     Ok(())
 }
 
-/// Apply random augmentations to an image
-fn apply_random_augmentations(img: &DynamicImage, rng: &mut impl Rng) -> DynamicImage {
+/// Apply random augmentations to an image, returning it with the augmentations used
+fn apply_random_augmentations(
+    img: &DynamicImage,
+    rng: &mut impl Rng,
+    config: &AugmentationConfig,
+) -> (DynamicImage, Vec<AugmentationType>) {
     let mut result = img.clone();
-    let augmentation_types = AugmentationType::all();
+    let augmentation_types = &config.augmentations;
 
     // Apply 2-4 random augmentations
     let num_augmentations = rng.random_range(2..=4);
@@ -270,11 +781,68 @@ fn apply_random_augmentations(img: &DynamicImage, rng: &mut impl Rng) -> Dynamic
         selected_augmentations.push(available_types.remove(index));
     }
 
-    for aug_type in selected_augmentations {
-        result = apply_augmentation(&result, aug_type, rng);
+    for &aug_type in &selected_augmentations {
+        result = apply_augmentation(
+            &result,
+            aug_type,
+            rng,
+            &config.intensities,
+            config.fill_color,
+        );
     }
 
-    result
+    (result, selected_augmentations)
+}
+
+/// Draw the names of the applied augmentations in a corner of the image
+fn draw_label(img: &DynamicImage, applied: &[AugmentationType], label: &Label) -> DynamicImage {
+    let text = applied
+        .iter()
+        .map(|aug_type| aug_type.name())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut canvas = img.to_rgba8();
+    let (width, height) = canvas.dimensions();
+
+    // Scale with the image so labels stay legible on large scans and fit on small ones
+    let scale = PxScale::from((height as f32 / 32.0).max(10.0));
+    let (text_width, text_height) = text_size(scale, &label.font, &text);
+    let padding = (scale.y / 4.0) as u32;
+    let box_width = (text_width + 2 * padding).min(width);
+    let box_height = (text_height + 2 * padding).min(height);
+
+    let x = match label.style.position {
+        LabelPosition::TopLeft | LabelPosition::BottomLeft => 0,
+        LabelPosition::TopRight | LabelPosition::BottomRight => width - box_width,
+    };
+    let y = match label.style.position {
+        LabelPosition::TopLeft | LabelPosition::TopRight => 0,
+        LabelPosition::BottomLeft | LabelPosition::BottomRight => height - box_height,
+    };
+
+    // A dark backing box keeps the text readable on any artwork
+    draw_filled_rect_mut(
+        &mut canvas,
+        Rect::at(x as i32, y as i32).of_size(box_width.max(1), box_height.max(1)),
+        Rgba([0, 0, 0, 255]),
+    );
+    let [r, g, b] = label.style.color.0;
+    draw_text_mut(
+        &mut canvas,
+        Rgba([r, g, b, 255]),
+        (x + padding) as i32,
+        (y + padding) as i32,
+        scale,
+        &label.font,
+        &text,
+    );
+
+    let labeled = DynamicImage::ImageRgba8(canvas);
+    if img.color().has_alpha() {
+        labeled
+    } else {
+        DynamicImage::ImageRgb8(labeled.to_rgb8())
+    }
 }
 
 /// Apply a specific augmentation to an image
@@ -282,60 +850,77 @@ fn apply_augmentation(
     img: &DynamicImage,
     aug_type: AugmentationType,
     rng: &mut impl Rng,
+    intensities: &AugmentationIntensities,
+    fill_color: Rgb<u8>,
 ) -> DynamicImage {
     match aug_type {
-        AugmentationType::Rotation => apply_rotation(img, rng),
-        AugmentationType::Brightness => apply_brightness(img, rng),
-        AugmentationType::Contrast => apply_contrast(img, rng),
-        AugmentationType::Saturation => apply_saturation(img, rng),
-        AugmentationType::Noise => apply_noise(img, rng),
-        AugmentationType::Blur => apply_blur(img, rng),
+        AugmentationType::Rotation => apply_rotation(img, rng, intensities.rotation, fill_color),
+        AugmentationType::Brightness => apply_brightness(img, rng, intensities.brightness),
+        AugmentationType::Contrast => apply_contrast(img, rng, intensities.contrast),
+        AugmentationType::Saturation => apply_saturation(img, rng, intensities.saturation),
+        AugmentationType::Noise => apply_noise(img, rng, intensities.noise),
+        AugmentationType::Blur => apply_blur(img, rng, intensities.blur),
         AugmentationType::Flip => apply_flip(img, rng),
+        AugmentationType::Perspective => {
+            apply_perspective(img, rng, intensities.perspective, fill_color)
+        }
+        AugmentationType::Shear => apply_shear(img, rng, intensities.shear, fill_color),
     }
 }
 
-/// Apply rotation augmentation (-15 to +15 degrees)
-fn apply_rotation(img: &DynamicImage, rng: &mut impl Rng) -> DynamicImage {
-    let angle: f32 = rng.random_range(-15.0..=15.0);
-    // Simple rotation implementation - for small angles, we can use a basic approach
-    if angle.abs() > 5.0 {
-        if rng.random_bool(0.5) {
-            img.rotate90()
-        } else {
-            img.rotate270()
-        }
-    } else {
-        img.clone() // For small angles, return original to avoid quality loss
+/// Apply rotation augmentation (-15 to +15 degrees by default)
+///
+/// The image keeps its size; corners rotated out of view are cut off and the
+/// areas exposed are filled with `fill_color`.
+fn apply_rotation(
+    img: &DynamicImage,
+    rng: &mut impl Rng,
+    range: IntensityRange,
+    fill_color: Rgb<u8>,
+) -> DynamicImage {
+    let angle = range.sample(rng);
+    if angle == 0.0 {
+        return img.clone();
     }
+
+    DynamicImage::ImageRgb8(rotate_about_center(
+        &img.to_rgb8(),
+        angle.to_radians(),
+        Interpolation::Bilinear,
+        fill_color,
+    ))
 }
 
 /// Apply brightness adjustment
-fn apply_brightness(img: &DynamicImage, rng: &mut impl Rng) -> DynamicImage {
-    let adjustment = rng.random_range(-30..=30);
+fn apply_brightness(img: &DynamicImage, rng: &mut impl Rng, range: IntensityRange) -> DynamicImage {
+    let adjustment = range.sample(rng).round() as i32;
     img.brighten(adjustment)
 }
 
 /// Apply contrast adjustment
-fn apply_contrast(img: &DynamicImage, rng: &mut impl Rng) -> DynamicImage {
-    let factor = rng.random_range(0.7..=1.3);
+fn apply_contrast(img: &DynamicImage, rng: &mut impl Rng, range: IntensityRange) -> DynamicImage {
+    let factor = range.sample(rng);
     adjust_contrast(img, factor)
 }
 
 /// Apply saturation adjustment
-fn apply_saturation(img: &DynamicImage, rng: &mut impl Rng) -> DynamicImage {
-    let factor = rng.random_range(0.5..=1.5);
+fn apply_saturation(img: &DynamicImage, rng: &mut impl Rng, range: IntensityRange) -> DynamicImage {
+    let factor = range.sample(rng);
     adjust_saturation(img, factor)
 }
 
 /// Apply noise
-fn apply_noise(img: &DynamicImage, rng: &mut impl Rng) -> DynamicImage {
-    let intensity = rng.random_range(5..=25);
+fn apply_noise(img: &DynamicImage, rng: &mut impl Rng, range: IntensityRange) -> DynamicImage {
+    let intensity = range.sample(rng).round() as u8;
     add_noise(img, intensity, rng)
 }
 
 /// Apply blur
-fn apply_blur(img: &DynamicImage, rng: &mut impl Rng) -> DynamicImage {
-    let sigma = rng.random_range(0.5..=2.0);
+fn apply_blur(img: &DynamicImage, rng: &mut impl Rng, range: IntensityRange) -> DynamicImage {
+    let sigma = range.sample(rng);
+    if sigma <= 0.0 {
+        return img.clone();
+    }
     img.blur(sigma)
 }
 
@@ -348,6 +933,85 @@ fn apply_flip(img: &DynamicImage, rng: &mut impl Rng) -> DynamicImage {
     }
 }
 
+/// Apply perspective distortion by moving each corner inward (up to 10% by default)
+fn apply_perspective(
+    img: &DynamicImage,
+    rng: &mut impl Rng,
+    range: IntensityRange,
+    fill_color: Rgb<u8>,
+) -> DynamicImage {
+    let rgb_img = img.to_rgb8();
+    let (width, height) = rgb_img.dimensions();
+    let (w, h) = (width as f32, height as f32);
+
+    let from = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)];
+    let mut jitter = |size: f32| range.sample(rng) * size;
+    let to = [
+        (jitter(w), jitter(h)),
+        (w - jitter(w), jitter(h)),
+        (w - jitter(w), h - jitter(h)),
+        (jitter(w), h - jitter(h)),
+    ];
+
+    match Projection::from_control_points(from, to) {
+        Some(projection) => DynamicImage::ImageRgb8(warp(
+            &rgb_img,
+            &projection,
+            Interpolation::Bilinear,
+            fill_color,
+        )),
+        None => img.clone(),
+    }
+}
+
+/// Apply a horizontal or vertical shear (factor -0.2 to +0.2 by default) around the image center
+fn apply_shear(
+    img: &DynamicImage,
+    rng: &mut impl Rng,
+    range: IntensityRange,
+    fill_color: Rgb<u8>,
+) -> DynamicImage {
+    let rgb_img = img.to_rgb8();
+    let (width, height) = rgb_img.dimensions();
+    let factor = range.sample(rng);
+
+    let matrix = if rng.random_bool(0.5) {
+        [
+            1.0,
+            factor,
+            -factor * height as f32 / 2.0,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        ]
+    } else {
+        [
+            1.0,
+            0.0,
+            0.0,
+            factor,
+            1.0,
+            -factor * width as f32 / 2.0,
+            0.0,
+            0.0,
+            1.0,
+        ]
+    };
+
+    match Projection::from_matrix(matrix) {
+        Some(projection) => DynamicImage::ImageRgb8(warp(
+            &rgb_img,
+            &projection,
+            Interpolation::Bilinear,
+            fill_color,
+        )),
+        None => img.clone(),
+    }
+}
+
 /// Adjust image contrast
 fn adjust_contrast(img: &DynamicImage, factor: f32) -> DynamicImage {
     let rgb_img = img.to_rgb8();
@@ -507,6 +1171,9 @@ async fn verify_images(train_dir: &Path) -> io::Result<(usize, usize)> {
 fn print_augmentation_stats(stats: &AugmentationStats, verified: bool) {
     println!("\n🎯 Augmentation Statistics:");
     println!("  📊 Total cards processed: {}", stats.total_cards);
+    if stats.skipped_cards > 0 {
+        println!("  ⏭️  Already augmented, skipped: {}", stats.skipped_cards);
+    }
     println!("  📷 Original images: {}", stats.total_original_images);
     println!(
         "  🔄 Total images after augmentation: {}",
@@ -526,6 +1193,16 @@ fn print_augmentation_stats(stats: &AugmentationStats, verified: bool) {
     };
     println!("\n📈 Dataset size multiplier: {:.1}x", multiplier);
 
+    if !stats.failed_directories.is_empty() {
+        println!(
+            "\n❌ Failed card directories: {}",
+            stats.failed_directories.len()
+        );
+        for dir in &stats.failed_directories {
+            println!("  - {}", dir.display());
+        }
+    }
+
     if verified {
         println!("\n🔍 Image verification:");
         println!("  ✅ Verified images: {}", stats.verified_images);
@@ -0,0 +1,121 @@
+use crate::tcg::TcgType;
+use image::{ImageFormat, RgbImage};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long a connectivity check waits for an API to answer
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of one environment check, with a short detail either way
+#[derive(Debug)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub result: Result<String, String>,
+}
+
+impl DoctorCheck {
+    fn new(name: impl Into<String>, result: Result<String, String>) -> Self {
+        Self {
+            name: name.into(),
+            result,
+        }
+    }
+}
+
+/// Run every environment and connectivity check for the given TCGs
+pub async fn run_checks(
+    client: &reqwest::Client,
+    path: &str,
+    tcgs: &[TcgType],
+) -> Vec<DoctorCheck> {
+    let mut checks = vec![DoctorCheck::new(
+        format!("Output path {} is writable", path),
+        check_writable(Path::new(path)),
+    )];
+
+    for tcg in tcgs {
+        let source = tcg.source();
+        if *tcg == TcgType::Custom {
+            checks.push(DoctorCheck::new(
+                format!("{} API", source.name()),
+                Ok("its URL comes from --source-url, so it is checked when fetching".to_string()),
+            ));
+            continue;
+        }
+        checks.push(DoctorCheck::new(
+            format!("{} API is reachable", source.name()),
+            check_reachable(client, source.api_url()).await,
+        ));
+    }
+
+    // Neither API needs credentials; report it so users stop looking for a key to set
+    checks.push(DoctorCheck::new(
+        "API keys",
+        Ok("none required, Scryfall and Grand Archive APIs are public".to_string()),
+    ));
+    checks.push(DoctorCheck::new(
+        "Image decoding (PNG and JPEG)",
+        check_image_codecs(),
+    ));
+
+    checks
+}
+
+/// Write and remove a probe file in the output path, or its closest existing ancestor
+fn check_writable(path: &Path) -> Result<String, String> {
+    let Some(existing) = path.ancestors().find(|dir| dir.is_dir()) else {
+        return Err("no existing parent directory".to_string());
+    };
+
+    let probe = existing.join(".tcg-fetch-doctor");
+    fs::write(&probe, b"ok")
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| format!("cannot write to {}: {}", existing.display(), e))?;
+
+    if existing == path {
+        Ok("exists and is writable".to_string())
+    } else {
+        Ok(format!(
+            "will be created under {}, which is writable",
+            existing.display()
+        ))
+    }
+}
+
+/// Send a lightweight HEAD request to an API endpoint
+async fn check_reachable(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    let response = client
+        .head(url)
+        .timeout(CONNECT_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("{}: {}", url, e))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(format!("{} answered {}", url, status))
+    } else {
+        Err(format!("{} answered {}", url, status))
+    }
+}
+
+/// Round-trip a tiny image through the PNG and JPEG codecs used for downloads
+fn check_image_codecs() -> Result<String, String> {
+    let image = RgbImage::from_pixel(2, 2, image::Rgb([200, 30, 30]));
+
+    for format in [ImageFormat::Png, ImageFormat::Jpeg] {
+        let mut encoded = Cursor::new(Vec::new());
+        image
+            .write_to(&mut encoded, format)
+            .map_err(|e| format!("failed to encode {:?}: {}", format, e))?;
+        let decoded = image::load_from_memory_with_format(encoded.get_ref(), format)
+            .map_err(|e| format!("failed to decode {:?}: {}", format, e))?;
+        if (decoded.width(), decoded.height()) != (2, 2) {
+            return Err(format!("{:?} round trip changed the image size", format));
+        }
+    }
+
+    Ok("test images decoded".to_string())
+}
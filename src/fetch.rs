@@ -0,0 +1,872 @@
+use crate::tcg::custom::CustomSource;
+use crate::tcg::mtg::{MtgSource, ScryfallDataset};
+use crate::tcg::{MetadataOptions, TcgType};
+use crate::utils;
+use crate::utils::cache::ImageCache;
+use crate::utils::card_list::CardList;
+use crate::utils::images::{
+    CardFilter, CardSort, CardTiming, DirNaming, DownloadOptions, HttpsPolicy, ImageProcessing,
+};
+use crate::utils::manifest::{ManifestFormat, ManifestWriter};
+use crate::utils::progress::ProgressReporter;
+use crate::utils::schedule::Schedule;
+use crate::utils::state::{Checkpoint, FailedDownload};
+use crate::utils::storage::ImageStore;
+use crate::utils::template::OutputTemplate;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Options for fetching card data and images for one or more TCGs
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    /// Trading card games to fetch, into the same path
+    pub tcgs: Vec<TcgType>,
+    /// Directory where the data is saved
+    pub path: String,
+    /// Data source for MTG cards
+    pub source: MtgSource,
+    /// Scryfall bulk data file to download
+    pub scryfall_dataset: ScryfallDataset,
+    /// Keep at most this many editions of each GA card
+    pub editions_per_card: Option<usize>,
+    /// Keep only GA editions created on or after this date
+    pub min_date: Option<NaiveDate>,
+    /// Indent the written GA card JSON for reading instead of writing it compactly
+    pub pretty_json: bool,
+    /// Card API and extraction paths for the custom TCG
+    pub custom: Option<CustomSource>,
+    /// Fetch the card JSON again when the cached file is older than this
+    pub max_age: Option<Duration>,
+    /// Always fetch the card JSON again, ignoring any cached file
+    pub refresh_json: bool,
+    /// Number of cards to fetch, or `all`
+    pub amount: Option<String>,
+    /// Maximum number of cards downloaded across all files
+    pub max_total: Option<usize>,
+    /// Maximum number of concurrent image downloads
+    pub threads: usize,
+    /// Adapt download concurrency to latency and errors, up to `threads`
+    pub adaptive_concurrency: bool,
+    /// Warn when no download completes for this long
+    pub stall_timeout: Option<Duration>,
+    /// Concurrent downloads allowed to any one host, within `threads`
+    pub per_host: Option<usize>,
+    /// Slow down requests to hosts that answer 429 until they recover
+    pub limit_rate_per_host: bool,
+    /// Times of day downloads run, pause or slow down
+    pub schedule: Option<Schedule>,
+    /// Estimated bytes of images decoded and processed at once
+    pub max_memory: Option<u64>,
+    /// Fallback host tried when a download from the primary image URL fails
+    pub image_mirror: Option<reqwest::Url>,
+    /// HTTP client shared by every request in the run
+    pub client: reqwest::Client,
+    /// Resizing and encoding of downloaded images
+    pub processing: ImageProcessing,
+    /// Group card directories by set code
+    pub group_by_set: bool,
+    /// Only build the card JSON file, skipping image downloads
+    pub json_only: bool,
+    /// Like `json_only`, also listing the ids of cards without a usable image
+    pub dry_validate: bool,
+    /// Reprocess the images already in `data/train` instead of fetching anything
+    pub convert_existing: bool,
+    /// Log dimensions and size of the first this many processed images
+    pub preview: usize,
+    /// Report download time percentiles and this many of the slowest cards
+    pub slowest: Option<usize>,
+    /// Re-validate every image once downloads finish and re-download corrupted ones
+    pub verify_after: bool,
+    /// Only retry the cards that failed in the previous run
+    pub retry_failed_only: bool,
+    /// Save the ids of finished cards every this many cards, so a crashed run resumes quickly
+    pub checkpoint_interval: Option<usize>,
+    /// Download new cards in batches of this many, setting up one batch at a time
+    pub batch_size: Option<usize>,
+    /// Order in which cards are downloaded
+    pub sort: Option<CardSort>,
+    /// Process cards ordered by id and write ordered manifests and failure lists
+    pub stable_order: bool,
+    /// Keep at most this many cards from each MTG set
+    pub sample_per_set: Option<usize>,
+    /// Which card objects (backs, tokens, ...) are kept
+    pub filter: CardFilter,
+    /// Remove card directories no longer present in the source data
+    pub delete_extraneous: bool,
+    /// Move extraneous directories to `.trash/` instead of deleting them
+    pub trash: bool,
+    /// Go ahead with destructive operations without asking
+    pub yes: bool,
+    /// Asked to approve destructive operations unless `yes` is set; without it
+    /// they are skipped, so the library never blocks waiting for input
+    pub confirm: Option<Confirm>,
+    /// Write a perceptual hash index to this path
+    pub hash_index: Option<PathBuf>,
+    /// Write a manifest of card metadata to this path
+    pub manifest: Option<PathBuf>,
+    /// Format of the manifest file
+    pub manifest_format: ManifestFormat,
+    /// Only download the cards named in this file, by id, slug or name
+    pub card_list: Option<PathBuf>,
+    /// Reuse source images cached in this directory, keyed by URL
+    pub cache_dir: Option<PathBuf>,
+    /// Hard-link processed images from this dataset instead of downloading them
+    pub reference_dir: Option<PathBuf>,
+    /// Directory for downloads awaiting validation, instead of the output path
+    pub temp_dir: Option<PathBuf>,
+    /// How card directories are named
+    pub dir_naming: DirNaming,
+    /// Save the parsed card list next to the source JSON and reuse it while unchanged
+    pub parse_cache: bool,
+    /// Skip or upgrade image URLs that are not HTTPS
+    pub require_https: Option<HttpsPolicy>,
+    /// Skip whole sets that already have a directory, with the set-grouped layout
+    pub only_new_sets: bool,
+    /// Where each card's image is written under `data/train`, from card metadata
+    pub output_template: Option<OutputTemplate>,
+    /// Upload processed images here instead of keeping them under `path`, which
+    /// then only holds the card JSON, run state and images awaiting upload
+    pub image_store: Option<Arc<dyn ImageStore>>,
+}
+
+/// Totals for a fetch run across all JSON files
+#[derive(Debug, Default, Serialize)]
+pub struct FetchSummary {
+    pub files: Vec<String>,
+    pub downloaded: usize,
+    pub skipped_existing: usize,
+    pub skipped_soon: usize,
+    pub failed: Vec<FailedDownload>,
+    pub redirect_failures: usize,
+    pub mirrored: usize,
+    /// Cards hard-linked from `reference_dir` instead of downloaded
+    pub linked: usize,
+    pub insecure_rejected: usize,
+    pub https_upgraded: usize,
+    pub auto_cropped: usize,
+    /// Existing images reprocessed by `convert_existing`
+    pub converted: usize,
+    /// Download time percentiles and slowest cards, with `slowest`
+    pub timing: Option<TimingReport>,
+}
+
+/// Download time percentiles and the slowest cards of a run
+#[derive(Debug, Serialize)]
+pub struct TimingReport {
+    pub cards: usize,
+    pub p50_secs: f64,
+    pub p95_secs: f64,
+    pub p99_secs: f64,
+    pub slowest: Vec<SlowCard>,
+}
+
+/// One of the slowest cards in a [`TimingReport`]
+#[derive(Debug, Serialize)]
+pub struct SlowCard {
+    pub id: String,
+    pub image_url: String,
+    pub secs: f64,
+}
+
+impl TimingReport {
+    /// Summarize per-card timings, returning `None` when no card was timed
+    fn new(timings: HashMap<String, CardTiming>, slowest: usize) -> Option<Self> {
+        let mut timings: Vec<(String, CardTiming)> = timings.into_iter().collect();
+        if timings.is_empty() {
+            return None;
+        }
+        timings.sort_by_key(|(_, timing)| Reverse(timing.elapsed));
+
+        // Nearest-rank percentile over the timings sorted slowest first
+        let percentile = |p: f64| {
+            let rank = ((p / 100.0) * timings.len() as f64).ceil() as usize;
+            timings[timings.len() - rank.max(1)].1.elapsed.as_secs_f64()
+        };
+
+        Some(Self {
+            cards: timings.len(),
+            p50_secs: percentile(50.0),
+            p95_secs: percentile(95.0),
+            p99_secs: percentile(99.0),
+            slowest: timings
+                .iter()
+                .take(slowest)
+                .map(|(id, timing)| SlowCard {
+                    id: id.clone(),
+                    image_url: timing.image_url.clone(),
+                    secs: timing.elapsed.as_secs_f64(),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl FetchSummary {
+    /// Print the skipped and failed counts for people reading the terminal
+    pub fn print_report(&self) {
+        if self.skipped_existing > 0 || self.skipped_soon > 0 {
+            println!();
+            if self.skipped_existing > 0 {
+                println!("Skipped {} cards (already existed)", self.skipped_existing);
+            }
+            if self.skipped_soon > 0 {
+                println!(
+                    "Skipped {} cards (soon.jpg placeholder images)",
+                    self.skipped_soon
+                );
+            }
+        }
+
+        if self.linked > 0 {
+            println!(
+                "\nHard-linked {} cards from the reference dataset, downloaded {}",
+                self.linked, self.downloaded
+            );
+        }
+
+        if self.insecure_rejected > 0 {
+            eprintln!(
+                "{} cards were skipped because their image URL is not HTTPS",
+                self.insecure_rejected
+            );
+        }
+
+        if self.redirect_failures > 0 {
+            eprintln!(
+                "{} downloads failed after exceeding the redirect limit",
+                self.redirect_failures
+            );
+        }
+
+        if let Some(timing) = &self.timing {
+            println!(
+                "\nDownload times over {} cards: p50 {:.2}s, p95 {:.2}s, p99 {:.2}s",
+                timing.cards, timing.p50_secs, timing.p95_secs, timing.p99_secs
+            );
+            if !timing.slowest.is_empty() {
+                println!("Slowest cards:");
+                for card in &timing.slowest {
+                    println!("  {:>8.2}s  {}  {}", card.secs, card.id, card.image_url);
+                }
+            }
+        }
+    }
+}
+
+/// Decides whether a destructive operation goes ahead, given a question describing it
+#[derive(Clone)]
+pub struct Confirm(Arc<dyn Fn(&str) -> bool + Send + Sync>);
+
+impl Confirm {
+    pub fn new(confirm: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(confirm))
+    }
+
+    fn ask(&self, question: &str) -> bool {
+        (self.0)(question)
+    }
+}
+
+impl fmt::Debug for Confirm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Confirm")
+    }
+}
+
+/// Card JSON left in `path` by earlier runs for TCGs not fetched now
+///
+/// Several TCGs can share one path, so their cards must not look extraneous.
+fn other_tcg_sources(sources: &[(TcgType, String)], path: &str) -> Vec<(TcgType, String)> {
+    let mut others = Vec::new();
+    for tcg_type in TcgType::value_variants() {
+        for stem in tcg_type.source().json_file_stems() {
+            let file = Path::new(path).join(format!("{}.json", stem));
+            let fetched = sources.iter().any(|(_, source)| Path::new(source) == file);
+            if !fetched && file.is_file() {
+                others.push((tcg_type.clone(), file.to_string_lossy().into_owned()));
+            }
+        }
+    }
+    others
+}
+
+/// Remove card directories whose ids are absent from the latest source JSON
+///
+/// Cards listed in the JSON of other TCGs sharing the path are kept too.
+fn delete_extraneous_cards(
+    sources: &[(TcgType, String)],
+    path: &str,
+    options: &DownloadOptions,
+    trash: bool,
+    yes: bool,
+    confirm: Option<&Confirm>,
+) -> io::Result<()> {
+    let group_by_set = options.group_by_set;
+    let others = other_tcg_sources(sources, path);
+    for (_, file) in &others {
+        println!("Keeping the cards listed in {}", file);
+    }
+
+    let mut expected = HashSet::new();
+    for (tcg_type, file) in sources.iter().chain(&others) {
+        let mut cards =
+            utils::parse_cache::load_cards(file, tcg_type, &options.filter, options.parse_cache)?
+                .cards;
+        utils::images::apply_dir_naming(&mut cards, options.dir_naming);
+        if options.filter.include_backs {
+            cards.extend(tcg_type.source().card_back());
+        }
+        for card in cards {
+            expected.insert(card.relative_dir(group_by_set));
+        }
+    }
+
+    let stale: Vec<PathBuf> = utils::files::list_card_directories(path, group_by_set)?
+        .into_iter()
+        .filter(|dir| !expected.contains(dir))
+        .collect();
+
+    if stale.is_empty() {
+        println!("No extraneous card directories found");
+        return Ok(());
+    }
+
+    let action = if trash { "Move to .trash" } else { "Delete" };
+    let question = format!("{} {} extraneous card directories?", action, stale.len());
+    if !yes && !confirm.is_some_and(|confirm| confirm.ask(&question)) {
+        println!("Skipping removal of extraneous card directories");
+        return Ok(());
+    }
+
+    let removed = utils::files::remove_card_directories(path, &stale, trash)?;
+    println!("Removed {} extraneous card directories", removed);
+    Ok(())
+}
+
+/// Fetch the card JSON for the selected TCGs and download their images
+pub async fn fetch(options: &FetchOptions) -> io::Result<FetchSummary> {
+    let path = options.path.as_str();
+    let client = options.client.clone();
+
+    if options.tcgs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No trading card game selected",
+        ));
+    }
+
+    if options.image_store.is_some() {
+        // These read or rewrite the images in place, which only exist locally
+        let local_only = [
+            (options.convert_existing, "--convert-existing"),
+            (options.verify_after, "--verify-after"),
+            (options.delete_extraneous, "--delete-extraneous"),
+            (options.only_new_sets, "--only-new-sets"),
+            (options.hash_index.is_some(), "--hash-index"),
+        ];
+        if let Some((_, flag)) = local_only.iter().find(|(enabled, _)| *enabled) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not supported when uploading to object storage", flag),
+            ));
+        }
+    }
+
+    // Listen for SIGUSR1 once for the whole run, so every step can be reported
+    let progress = Arc::new(ProgressReporter::new());
+
+    // Ensure the output directory exists
+    utils::files::ensure_directories(path)?;
+
+    if options.convert_existing {
+        progress.set_phase("Converting existing images");
+        println!("Converting existing images in {}/data/train", path);
+        let converted = utils::images::convert_existing_images(
+            path,
+            options.group_by_set,
+            &options.processing,
+        )?;
+        println!(
+            "Converted {} images, {} failed",
+            converted.converted, converted.failed
+        );
+        return Ok(FetchSummary {
+            converted: converted.converted,
+            ..Default::default()
+        });
+    }
+
+    // Fetch and download the JSON files for each selected TCG; their file names
+    // differ, so several TCGs can share one path
+    let max_age = if options.refresh_json {
+        println!("Refreshing card JSON (--refresh-json), cached files will be replaced");
+        Some(Duration::ZERO)
+    } else {
+        options.max_age
+    };
+    let metadata_options = MetadataOptions {
+        source: options.source,
+        scryfall_dataset: options.scryfall_dataset,
+        editions_per_card: options.editions_per_card,
+        min_date: options.min_date,
+        max_age,
+        pretty_json: options.pretty_json,
+        custom: options.custom.clone(),
+    };
+    let mut sources = Vec::new();
+    for tcg_type in &options.tcgs {
+        progress.set_phase(format!("Fetching {} card JSON", tcg_type.source().name()));
+        let tcg_files = tcg_type
+            .source()
+            .fetch_metadata(&client, path, &metadata_options)
+            .await
+            .map_err(|e| io::Error::new(e.kind(), format!("Error fetching bulk data: {}", e)))?;
+        sources.extend(tcg_files.into_iter().map(|file| (tcg_type.clone(), file)));
+    }
+    let files: Vec<String> = sources.iter().map(|(_, file)| file.clone()).collect();
+
+    println!("\nDownloaded JSON files:");
+    for file in &files {
+        println!("  - {}", file);
+    }
+
+    let mut summary = FetchSummary {
+        files: files.clone(),
+        ..Default::default()
+    };
+
+    if options.json_only || options.dry_validate {
+        for (tcg_type, file) in &sources {
+            match utils::parse_cache::load_cards(
+                file,
+                tcg_type,
+                &options.filter,
+                options.parse_cache,
+            ) {
+                Ok(loaded) => {
+                    println!("{}: {} cards with images", file, loaded.cards.len());
+                    loaded.report_exclusions();
+                    if options.dry_validate && !loaded.imageless.is_empty() {
+                        println!("Cards without a usable image:");
+                        for id in &loaded.imageless {
+                            println!("  {}", id);
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Error reading {}: {}", file, e),
+            }
+        }
+        return Ok(summary);
+    }
+
+    let only_ids = if options.retry_failed_only {
+        match utils::state::load_failed_state(path)? {
+            Some(failed) if !failed.is_empty() => {
+                println!("Retrying {} previously failed downloads", failed.len());
+                Some(Arc::new(failed.into_iter().map(|card| card.id).collect()))
+            }
+            _ => {
+                println!("No failed downloads recorded from a previous run");
+                return Ok(summary);
+            }
+        }
+    } else {
+        None
+    };
+
+    let card_list = match &options.card_list {
+        Some(list_path) => {
+            let card_list = CardList::load(list_path)?;
+            println!(
+                "Limiting downloads to {} cards from {}",
+                card_list.requested(),
+                list_path.display()
+            );
+            Some(Arc::new(card_list))
+        }
+        None => None,
+    };
+
+    let manifest = match &options.manifest {
+        Some(manifest_path) => Some(Arc::new(ManifestWriter::create(
+            manifest_path,
+            options.manifest_format,
+            options.stable_order,
+        )?)),
+        None => None,
+    };
+
+    let checkpoint = match options.checkpoint_interval {
+        Some(interval) => {
+            let checkpoint = Checkpoint::open(path, interval)?;
+            if checkpoint.completed_count() > 0 {
+                println!(
+                    "Resuming from checkpoint: {} cards already finished",
+                    checkpoint.completed_count()
+                );
+            }
+            Some(Arc::new(checkpoint))
+        }
+        None => None,
+    };
+
+    let cache = match &options.cache_dir {
+        Some(cache_dir) => Some(ImageCache::new(cache_dir)?),
+        None => None,
+    };
+
+    let download_options = DownloadOptions {
+        client,
+        amount: options.amount.clone(),
+        remaining_total: options
+            .max_total
+            .map(|max_total| Arc::new(AtomicUsize::new(max_total))),
+        thread_count: options.threads,
+        processing: options.processing.clone(),
+        // Replaced with each file's own TCG below
+        tcg_type: options.tcgs[0].clone(),
+        group_by_set: options.group_by_set,
+        sort: options.sort,
+        stable_order: options.stable_order,
+        sample_per_set: options.sample_per_set,
+        filter: options.filter,
+        adaptive_concurrency: options.adaptive_concurrency,
+        stall_timeout: options.stall_timeout,
+        image_mirror: options.image_mirror.clone(),
+        temp_dir: options.temp_dir.clone(),
+        dir_naming: options.dir_naming,
+        parse_cache: options.parse_cache,
+        require_https: options.require_https,
+        only_new_sets: options.only_new_sets,
+        max_memory: options.max_memory,
+        per_host: options.per_host,
+        limit_rate_per_host: options.limit_rate_per_host,
+        schedule: options.schedule.clone(),
+        image_store: options.image_store.clone(),
+        only_ids,
+        card_list: card_list.clone(),
+        manifest: manifest.clone(),
+        checkpoint: checkpoint.clone(),
+        progress: Some(progress.clone()),
+        batch_size: options.batch_size,
+        cache,
+        reference_dir: options.reference_dir.clone(),
+        output_template: options.output_template.clone(),
+        preview: options.preview,
+        track_timings: options.slowest.is_some(),
+    };
+
+    if options.delete_extraneous {
+        if let Err(e) = delete_extraneous_cards(
+            &sources,
+            path,
+            &download_options,
+            options.trash,
+            options.yes,
+            options.confirm.as_ref(),
+        ) {
+            eprintln!("Error removing extraneous cards: {}", e);
+        }
+    }
+
+    // Each file is processed with the settings of the TCG it belongs to
+    let options_for = |tcg_type: &TcgType, base: &DownloadOptions| DownloadOptions {
+        tcg_type: tcg_type.clone(),
+        ..base.clone()
+    };
+
+    // Keyed by card id, so a re-download after verification replaces the first attempt
+    let mut timings = HashMap::new();
+    // Cleared when a file stops early, leaving some of its cards unaccounted for
+    let mut all_finished = true;
+    for (tcg_type, file) in &sources {
+        println!("\nProcessing file: {}", file);
+        progress.set_phase(format!("Processing {}", file));
+        let file_options = options_for(tcg_type, &download_options);
+        match utils::images::download_card_images(file, path, &file_options).await {
+            Ok(file_summary) => {
+                summary.downloaded += file_summary.downloaded;
+                summary.skipped_existing += file_summary.skipped_existing;
+                summary.skipped_soon += file_summary.skipped_soon;
+                summary.failed.extend(file_summary.failed);
+                summary.redirect_failures += file_summary.redirect_failures;
+                summary.mirrored += file_summary.mirrored;
+                summary.linked += file_summary.linked;
+                summary.insecure_rejected += file_summary.insecure_rejected;
+                summary.https_upgraded += file_summary.https_upgraded;
+                summary.auto_cropped += file_summary.auto_cropped;
+                timings.extend(file_summary.timings);
+            }
+            Err(e) => {
+                eprintln!("Error downloading images: {}", e);
+                all_finished = false;
+            }
+        }
+    }
+
+    if let Some(card_list) = &card_list {
+        let missing = card_list.missing();
+        if !missing.is_empty() {
+            println!(
+                "\n{} cards from the card list were not found in the source:",
+                missing.len()
+            );
+            for entry in missing {
+                println!("  {}", entry);
+            }
+        }
+    }
+
+    if options.verify_after {
+        progress.set_phase("Verifying images");
+        let mut checked = 0;
+        let mut corrupted = HashSet::new();
+        for (tcg_type, file) in &sources {
+            let file_options = options_for(tcg_type, &download_options);
+            match utils::images::verify_card_images(file, path, &file_options) {
+                Ok(verify_summary) => {
+                    checked += verify_summary.checked;
+                    corrupted.extend(verify_summary.corrupted);
+                }
+                Err(e) => eprintln!("Error verifying images: {}", e),
+            }
+        }
+        println!(
+            "\nVerified {} images, {} corrupted",
+            checked,
+            corrupted.len()
+        );
+
+        // The source JSON is on hand, so fetch the corrupted cards again
+        if !corrupted.is_empty() {
+            println!("Re-downloading {} corrupted cards", corrupted.len());
+            progress.set_phase("Re-downloading corrupted cards");
+            let retry_options = DownloadOptions {
+                only_ids: Some(Arc::new(corrupted)),
+                // Repairs replace images already counted against --max-total
+                remaining_total: None,
+                // The corrupted cards were checkpointed when first downloaded
+                checkpoint: None,
+                ..download_options.clone()
+            };
+            for (tcg_type, file) in &sources {
+                let file_options = options_for(tcg_type, &retry_options);
+                match utils::images::download_card_images(file, path, &file_options).await {
+                    Ok(file_summary) => {
+                        summary.downloaded += file_summary.downloaded;
+                        summary.failed.extend(file_summary.failed);
+                        summary.redirect_failures += file_summary.redirect_failures;
+                        summary.mirrored += file_summary.mirrored;
+                        timings.extend(file_summary.timings);
+                    }
+                    Err(e) => {
+                        eprintln!("Error re-downloading images: {}", e);
+                        all_finished = false;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(slowest) = options.slowest {
+        summary.timing = TimingReport::new(timings, slowest);
+    }
+
+    // Once every file is done the images on disk show what is done; otherwise
+    // the checkpoint is what lets the next run resume
+    if let (true, Some(checkpoint)) = (all_finished, &checkpoint) {
+        if let Err(e) = checkpoint.remove() {
+            eprintln!("Error removing checkpoint: {}", e);
+        }
+    }
+
+    // A file that stopped early may have left earlier failures unretried, so keep them
+    let saved_state = if all_finished {
+        utils::state::save_failed_state(path, &summary.failed)
+    } else {
+        utils::state::merge_failed_state(path, &summary.failed)
+    };
+    if let Err(e) = saved_state {
+        eprintln!("Error saving failed download state: {}", e);
+    }
+
+    if let Some(manifest) = &manifest {
+        if let Err(e) = manifest.finish() {
+            eprintln!("Error writing manifest: {}", e);
+        }
+    }
+
+    if let Some(hash_index) = &options.hash_index {
+        if let Err(e) = utils::hash_index::write_hash_index(
+            path,
+            options.group_by_set,
+            &options.processing.primary_image_name(),
+            hash_index,
+        ) {
+            eprintln!("Error writing hash index: {}", e);
+        }
+    }
+
+    // Count and display the number of directories in train folder
+    if options.image_store.is_none() {
+        if let Err(e) = utils::files::count_train_directories(path, options.group_by_set) {
+            eprintln!("Error counting train directories: {}", e);
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::images::{Channels, OutputFormat};
+    use std::fs;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tcg-fetch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn download_options() -> DownloadOptions {
+        DownloadOptions {
+            client: reqwest::Client::new(),
+            amount: None,
+            remaining_total: None,
+            thread_count: 1,
+            processing: ImageProcessing {
+                width: 32,
+                height: 44,
+                format: OutputFormat::Jpeg,
+                channels: Channels::Rgb,
+                max_dimension: None,
+                strip_metadata: false,
+                auto_contrast: None,
+                sharpen: None,
+                auto_crop: false,
+                sizes: Vec::new(),
+                convert_srgb: false,
+            },
+            tcg_type: TcgType::Ga,
+            group_by_set: false,
+            sort: None,
+            stable_order: false,
+            sample_per_set: None,
+            filter: CardFilter::default(),
+            adaptive_concurrency: false,
+            stall_timeout: None,
+            image_mirror: None,
+            temp_dir: None,
+            dir_naming: DirNaming::default(),
+            parse_cache: false,
+            require_https: None,
+            only_new_sets: false,
+            max_memory: None,
+            per_host: None,
+            limit_rate_per_host: false,
+            schedule: None,
+            image_store: None,
+            only_ids: None,
+            card_list: None,
+            manifest: None,
+            checkpoint: None,
+            progress: None,
+            batch_size: None,
+            cache: None,
+            reference_dir: None,
+            output_template: None,
+            preview: 0,
+            track_timings: false,
+        }
+    }
+
+    #[test]
+    fn delete_extraneous_keeps_cards_of_other_tcgs_in_the_path() {
+        let dir = test_dir("delete-extraneous");
+        let path = dir.to_str().unwrap();
+        fs::write(
+            dir.join("ga_cards.json"),
+            r#"[{"slug": "ga-card", "image": "https://example.com/ga.jpg"}]"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("custom_cards.json"),
+            r#"[{"id": "custom-card", "image_url": "https://example.com/custom.jpg"}]"#,
+        )
+        .unwrap();
+        for card in ["ga-card", "custom-card", "stale-card"] {
+            fs::create_dir_all(dir.join("data/train").join(card)).unwrap();
+        }
+
+        // Only GA is fetched, while the custom cards came from an earlier run
+        let sources = vec![(
+            TcgType::Ga,
+            dir.join("ga_cards.json").to_string_lossy().into_owned(),
+        )];
+        delete_extraneous_cards(&sources, path, &download_options(), false, true, None).unwrap();
+
+        let train = dir.join("data/train");
+        assert!(train.join("ga-card").is_dir());
+        assert!(train.join("custom-card").is_dir());
+        assert!(!train.join("stale-card").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn max_total_is_only_spent_on_downloaded_cards() {
+        let dir = test_dir("max-total");
+        let path = dir.to_str().unwrap();
+
+        // Nothing listens on port 1, so every card of the first file fails
+        let failing = dir.join("failing.json");
+        fs::write(
+            &failing,
+            r#"[{"slug": "fail-1", "image": "http://127.0.0.1:1/fail-1.png"},
+                {"slug": "fail-2", "image": "http://127.0.0.1:1/fail-2.png"}]"#,
+        )
+        .unwrap();
+
+        // The second file's images are served from the cache, without the network
+        let cache = ImageCache::new(&dir.join("cache")).unwrap();
+        let mut png = Vec::new();
+        image::RgbImage::from_fn(32, 44, |x, y| image::Rgb([x as u8 * 8, y as u8 * 5, 128]))
+            .write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let cached = dir.join("cached.json");
+        let mut cards = Vec::new();
+        for id in ["cached-1", "cached-2"] {
+            let url = format!("http://127.0.0.1:1/{}.png", id);
+            cache.store(&url, &png).unwrap();
+            cards.push(serde_json::json!({"slug": id, "image": url}));
+        }
+        fs::write(&cached, serde_json::to_string(&cards).unwrap()).unwrap();
+
+        let options = DownloadOptions {
+            remaining_total: Some(Arc::new(AtomicUsize::new(2))),
+            cache: Some(cache),
+            ..download_options()
+        };
+        let first = utils::images::download_card_images(failing.to_str().unwrap(), path, &options)
+            .await
+            .unwrap();
+        assert_eq!(first.failed.len(), 2);
+        let second = utils::images::download_card_images(cached.to_str().unwrap(), path, &options)
+            .await
+            .unwrap();
+        assert_eq!(second.downloaded, 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
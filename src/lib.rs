@@ -0,0 +1,36 @@
+//! Fetch trading card game data and build image datasets
+//!
+//! The `tcg-fetch` binary is a thin wrapper around this library; other Rust
+//! programs can call [`fetch`], [`augment`] and [`download_images`] directly
+//! with typed option structs.
+
+pub mod augmentation;
+pub mod doctor;
+pub mod fetch;
+pub mod tcg;
+pub mod utils;
+
+use std::io;
+
+pub use augmentation::AugmentOptions;
+pub use fetch::{FetchOptions, FetchSummary};
+pub use utils::images::{DownloadOptions, DownloadSummary};
+
+/// Fetch the card JSON for the selected TCGs and download their images
+pub async fn fetch(options: &FetchOptions) -> io::Result<FetchSummary> {
+    fetch::fetch(options).await
+}
+
+/// Generate augmented versions of every image in a dataset
+pub async fn augment(options: AugmentOptions) -> Result<(), Box<dyn std::error::Error>> {
+    augmentation::augment_dataset(options).await
+}
+
+/// Download and process the images listed in one card JSON file
+pub async fn download_images(
+    json_path: &str,
+    output_dir: &str,
+    options: &DownloadOptions,
+) -> io::Result<DownloadSummary> {
+    utils::images::download_card_images(json_path, output_dir, options).await
+}
@@ -1,154 +1,1039 @@
-use clap::{Parser, Subcommand};
+use chrono::NaiveDate;
+use clap::builder::PossibleValue;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde_json::json;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
 use std::thread;
+use std::time::Duration;
 
-mod augmentation;
-mod tcg;
-mod utils;
-
-use tcg::TcgType;
+use tcg_fetch::fetch::Confirm;
+use tcg_fetch::tcg::custom::CustomSource;
+use tcg_fetch::tcg::mtg::{Finish, ImageStatus, MtgSource, ScryfallDataset};
+use tcg_fetch::tcg::TcgType;
+use tcg_fetch::utils;
+use tcg_fetch::utils::http::HttpOptions;
+use tcg_fetch::utils::images::{
+    AutoContrast, CardFilter, CardSort, Channels, DirNaming, HttpsPolicy, ImageProcessing,
+    OutputFormat,
+};
+use tcg_fetch::utils::json_path::JsonPath;
+use tcg_fetch::utils::manifest::ManifestFormat;
+use tcg_fetch::utils::schedule::Schedule;
+use tcg_fetch::utils::storage::{ImageStore, S3Store};
+use tcg_fetch::utils::template::OutputTemplate;
+use tcg_fetch::{augmentation, AugmentOptions, FetchOptions};
 
 /// Simple program to fetch trading card game data from various APIs
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// Read default option values from a TOML config file (command line flags take precedence)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// A trading card game named on the command line, or `all` of them
+///
+/// Every `TcgType` is accepted as is, so a new game needs no change here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TcgChoice {
+    Tcg(TcgType),
+    All,
+}
+
+impl ValueEnum for TcgChoice {
+    fn value_variants<'a>() -> &'a [Self] {
+        static VARIANTS: OnceLock<Vec<TcgChoice>> = OnceLock::new();
+        VARIANTS.get_or_init(|| {
+            TcgType::value_variants()
+                .iter()
+                .cloned()
+                .map(TcgChoice::Tcg)
+                .chain([TcgChoice::All])
+                .collect()
+        })
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            TcgChoice::Tcg(tcg) => tcg.to_possible_value(),
+            TcgChoice::All => Some(PossibleValue::new("all").help("Every built-in game")),
+        }
+    }
+}
+
+impl TcgChoice {
+    /// Expand `all` and drop repeats, keeping the order the games were given in
+    fn resolve(choices: &[TcgChoice]) -> Vec<TcgType> {
+        let mut tcgs: Vec<TcgType> = Vec::new();
+        for choice in choices {
+            let expanded = match choice {
+                TcgChoice::Tcg(tcg) => vec![tcg.clone()],
+                TcgChoice::All => built_in_tcgs(),
+            };
+            for tcg in expanded {
+                if !tcgs.contains(&tcg) {
+                    tcgs.push(tcg);
+                }
+            }
+        }
+        tcgs
+    }
+}
+
+/// Every game with dedicated support, leaving out the custom source
+fn built_in_tcgs() -> Vec<TcgType> {
+    TcgType::value_variants()
+        .iter()
+        .filter(|tcg| **tcg != TcgType::Custom)
+        .cloned()
+        .collect()
+}
+
+/// Arguments of the `fetch` command
+#[derive(clap::Args, Debug)]
+struct FetchArgs {
+    /// Trading card games to fetch data for, or `all`
+    #[arg(value_enum, required = true, num_args = 1..)]
+    tcg: Vec<TcgChoice>,
+    /// Path where to save the data, or an s3://bucket/prefix URL to upload images to
+    #[arg(short, long, default_value = "tcg-data")]
+    path: String,
+
+    /// Local directory for card JSON and images awaiting upload when --path is an s3:// URL
+    #[arg(long, value_name = "DIR", default_value = "tcg-staging")]
+    staging_dir: String,
+
+    /// Data source for MTG cards
+    #[arg(long, value_enum, default_value_t = MtgSource::Scryfall)]
+    source: MtgSource,
+
+    /// Amount of cards to fetch
+    #[arg(short, long, default_value = "all")]
+    amount: Option<String>,
+
+    /// Maximum number of cards downloaded across all files in this run
+    #[arg(long, value_name = "N")]
+    max_total: Option<usize>,
+
+    /// Scryfall bulk data file to download (MTG with --source scryfall only)
+    #[arg(long, value_enum, default_value_t = ScryfallDataset::AllCards)]
+    scryfall_dataset: ScryfallDataset,
+
+    /// Keep at most this many editions of each card (GA only)
+    #[arg(long)]
+    editions_per_card: Option<NonZeroUsize>,
+
+    /// Write ga_cards.json indented for reading instead of compact (GA only)
+    #[arg(long, default_value_t = false)]
+    pretty_json: bool,
+
+    /// Keep only editions created on or after this date, as YYYY-MM-DD (GA only)
+    #[arg(long, value_name = "DATE")]
+    min_date: Option<NaiveDate>,
+
+    /// URL of the JSON card list to fetch (custom only)
+    #[arg(long, value_name = "URL", requires_all = ["id_path", "image_path"])]
+    source_url: Option<reqwest::Url>,
+
+    /// Path to the array of cards in the --source-url document, e.g. $.data (custom only)
+    #[arg(long, value_name = "PATH", default_value = "$", value_parser = JsonPath::parse)]
+    records_path: JsonPath,
+
+    /// Path to the id in each card record, e.g. $.slug (custom only)
+    #[arg(long, value_name = "PATH", value_parser = JsonPath::parse, requires = "source_url")]
+    id_path: Option<JsonPath>,
+
+    /// Path to the image URL in each card record, e.g. $.images.large (custom only)
+    #[arg(long, value_name = "PATH", value_parser = JsonPath::parse, requires = "source_url")]
+    image_path: Option<JsonPath>,
+
+    /// Path to the name in each card record (custom only)
+    #[arg(long, value_name = "PATH", value_parser = JsonPath::parse, requires = "source_url")]
+    name_path: Option<JsonPath>,
+
+    /// Fetch the card JSON again when the cached file is older than this, e.g. 7d or 12h
+    #[arg(long, value_name = "DURATION", value_parser = utils::files::parse_duration)]
+    max_age: Option<Duration>,
+
+    /// Always fetch the card JSON again instead of reusing a cached file
+    #[arg(long, visible_alias = "overwrite-json", default_value_t = false)]
+    refresh_json: bool,
+
+    /// Number of threads to use for downloading images (defaults to number of CPU cores, 0 also means all cores)
+    #[arg(short, long, default_value_t = thread::available_parallelism().map_or(1, |p| p.get()))]
+    threads: usize,
+
+    /// Adapt download concurrency to observed latency and errors, up to --threads
+    #[arg(long, default_value_t = false)]
+    adaptive_concurrency: bool,
+
+    /// Concurrent downloads allowed to any one image host, within --threads
+    #[arg(long, value_name = "N", alias = "parallel-downloads-per-host")]
+    per_host: Option<NonZeroUsize>,
+
+    /// Slow down requests to a host that answers 429, restoring the rate once it recovers
+    #[arg(long, default_value_t = false)]
+    limit_rate_per_host: bool,
+
+    /// Save the ids of finished cards every N cards so a crashed run resumes without rescanning
+    #[arg(long, value_name = "N")]
+    checkpoint_interval: Option<NonZeroUsize>,
+
+    /// Download new cards in batches of N, setting up and tracking one batch at a time
+    #[arg(long, value_name = "N")]
+    batch_size: Option<NonZeroUsize>,
+
+    /// Local times downloads run, e.g. 22:00-07:00 or 22:00-07:00,07:00-09:00=2 (paused outside them)
+    #[arg(long, value_name = "WINDOWS", value_parser = Schedule::parse)]
+    schedule: Option<Schedule>,
+
+    /// Warn when no download completes for this long, e.g. 30s (0 disables)
+    #[arg(long, value_name = "DURATION", default_value = "30s", value_parser = utils::files::parse_duration)]
+    stall_timeout: Duration,
+
+    /// Limit the estimated memory of images decoded and processed at once, e.g. 512M or 2G
+    #[arg(long, value_name = "SIZE", value_parser = utils::files::parse_byte_size)]
+    max_memory: Option<u64>,
+
+    /// Skip image URLs that are not HTTPS, or rewrite http:// to https:// with `upgrade`
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, default_missing_value = "reject")]
+    require_https: Option<HttpsPolicy>,
+
+    /// Fallback image host tried when a download fails, e.g. https://mirror.example.com
+    #[arg(long, value_name = "URL")]
+    image_mirror: Option<reqwest::Url>,
+
+    /// Extra header attached to all requests ("Name: Value"), may be repeated
+    #[arg(long, value_parser = utils::http::parse_header)]
+    header: Vec<(HeaderName, HeaderValue)>,
+
+    /// Maximum idle connections kept open per host; raise alongside --threads
+    #[arg(long)]
+    pool_size: Option<usize>,
+
+    /// Multiplex requests over HTTP/2 when the server supports it (falls back to HTTP/1.1)
+    #[arg(long, default_value_t = false)]
+    http2: bool,
+
+    /// Maximum redirects followed per request, 0 to not follow redirects
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    max_redirects: usize,
+
+    /// Width for processed images (10-10000 pixels)
+    #[arg(long, default_value_t = utils::images::DEFAULT_WIDTH, value_parser = utils::images::parse_image_dimension)]
+    width: u32,
+
+    /// Height for processed images (10-10000 pixels)
+    #[arg(long, default_value_t = utils::images::DEFAULT_HEIGHT, value_parser = utils::images::parse_image_dimension)]
+    height: u32,
+
+    /// Write one image per size from a single decode, e.g. 224x224,384x384, named 0000_224x224.jpg
+    #[arg(long, value_name = "WxH,...", value_delimiter = ',', value_parser = utils::images::parse_image_size, conflicts_with_all = ["width", "height", "convert_existing"])]
+    sizes: Vec<(u32, u32)>,
+
+    /// Downscale source images whose longest side exceeds this many pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_image_dimension: Option<u32>,
+
+    /// Image format for processed images
+    #[arg(long, value_enum, default_value_t = OutputFormat::Jpeg)]
+    image_format: OutputFormat,
+
+    /// Color channels to keep (rgba only applies to formats with alpha, e.g. png)
+    #[arg(long, value_enum, default_value_t = Channels::Rgb)]
+    channels: Channels,
+
+    /// Strip JFIF, EXIF, ICC and comment segments from JPEG output for the smallest files
+    #[arg(long, default_value_t = false)]
+    strip_metadata: bool,
+
+    /// Equalize the histogram of source images before resizing (default mode: luminance)
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, default_missing_value = "luminance")]
+    auto_contrast: Option<AutoContrast>,
+
+    /// Crop photos and scans to the detected card boundary before resizing
+    #[arg(long, default_value_t = false)]
+    auto_crop: bool,
+
+    /// Convert sources with an embedded ICC color profile to sRGB before processing
+    #[arg(long, default_value_t = false)]
+    convert_srgb: bool,
+
+    /// Sharpen images after resizing with this unsharp-mask radius in pixels, e.g. 1.0
+    #[arg(long, value_name = "AMOUNT", value_parser = utils::images::parse_sharpen)]
+    sharpen: Option<f32>,
+
+    /// Group card directories by set code (data/train/<set>/<card-id>, MTG only)
+    #[arg(long, default_value_t = false)]
+    group_by_set: bool,
+
+    /// Skip sets that already have a directory instead of checking each card (needs --group-by-set)
+    #[arg(long, default_value_t = false, requires = "group_by_set")]
+    only_new_sets: bool,
+
+    /// How card directories are named (collector: <set>-<collector_number>, MTG only)
+    #[arg(long, value_enum, default_value_t = DirNaming::Id, conflicts_with = "output_template")]
+    dir_naming: DirNaming,
+
+    /// Only build the card JSON file, skipping image downloads
+    #[arg(long, default_value_t = false)]
+    json_only: bool,
+
+    /// Like --json-only, and list the cards that have no usable image
+    #[arg(long, default_value_t = false)]
+    dry_validate: bool,
+
+    /// Reprocess the images already downloaded to the current size and format, without network access
+    #[arg(long, default_value_t = false, conflicts_with_all = ["json_only", "dry_validate", "output_template", "retry_failed_only"])]
+    convert_existing: bool,
+
+    /// Log the decoded dimensions and file size of the first N processed images
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    preview: usize,
+
+    /// Report download time percentiles and the N slowest cards at the end of the run
+    #[arg(long, value_name = "N")]
+    slowest: Option<usize>,
+
+    /// Re-validate every image once downloads finish and re-download corrupted ones
+    #[arg(long, default_value_t = false)]
+    verify_after: bool,
+
+    /// Only retry the cards that failed to download in the previous run
+    #[arg(long, default_value_t = false)]
+    retry_failed_only: bool,
+
+    /// Sort cards before downloading (defaults to file order)
+    #[arg(long, value_enum)]
+    sort: Option<CardSort>,
+
+    /// Process cards in id order and write manifests and failure lists sorted by id
+    #[arg(long, default_value_t = false)]
+    stable_order: bool,
+
+    /// Download at most N cards from each set for a balanced dataset, before --amount (MTG only)
+    #[arg(long, value_name = "N")]
+    sample_per_set: Option<NonZeroUsize>,
+
+    /// Also download double-faced card backs and the generic card back (MTG only)
+    #[arg(long, default_value_t = false)]
+    include_backs: bool,
+
+    /// Keep tokens, emblems and art cards, which are excluded by default (MTG only)
+    #[arg(long, default_value_t = false)]
+    include_tokens: bool,
+
+    /// Skip digital-only printings such as Arena and MTGO cards (MTG only)
+    #[arg(long, default_value_t = false)]
+    exclude_digital: bool,
+
+    /// Skip promotional printings such as prerelease and buy-a-box promos (MTG only)
+    #[arg(long, default_value_t = false, overrides_with = "include_promos")]
+    exclude_promos: bool,
+
+    /// Keep promotional printings, the default; overrides --exclude-promos (e.g. from a config file)
+    #[arg(long, default_value_t = false, overrides_with = "exclude_promos")]
+    include_promos: bool,
+
+    /// Keep only cards printed in this finish (MTG only)
+    #[arg(long, value_enum)]
+    finish: Option<Finish>,
+
+    /// Skip cards whose Scryfall image_status is below this, e.g. highres-scan for full scans only (MTG only)
+    #[arg(long, value_enum, default_value_t = ImageStatus::Lowres)]
+    min_image_status: ImageStatus,
+
+    /// Keep one printing per card (by Scryfall oracle id): the best image, then the newest (MTG only)
+    #[arg(long, default_value_t = false)]
+    dedupe_by_oracle_id: bool,
+
+    /// Remove card directories that no longer exist in the source data
+    #[arg(long, default_value_t = false)]
+    delete_extraneous: bool,
+
+    /// Move extraneous card directories to .trash/ instead of deleting them
+    #[arg(long, default_value_t = false, requires = "delete_extraneous")]
+    trash: bool,
+
+    /// Skip the confirmation prompt for destructive operations
+    #[arg(short, long, default_value_t = false)]
+    yes: bool,
+
+    /// Write a perceptual hash index (card_id -> dHash) to this path (.json or .csv)
+    #[arg(long)]
+    hash_index: Option<String>,
+
+    /// Write a manifest of card metadata to this path
+    #[arg(long)]
+    manifest: Option<String>,
+
+    /// Format of the manifest file
+    #[arg(long, value_enum, default_value_t = ManifestFormat::Json, requires = "manifest")]
+    manifest_format: ManifestFormat,
+
+    /// Image path template under data/train using {id}, {name}, {set} and {rarity}, e.g. "{set}/{rarity}/{id}"
+    #[arg(long, value_parser = OutputTemplate::parse, conflicts_with_all = ["group_by_set", "delete_extraneous", "hash_index"])]
+    output_template: Option<OutputTemplate>,
+
+    /// Save the parsed card list next to the card JSON and reuse it while the JSON is unchanged
+    #[arg(long, default_value_t = false)]
+    parse_cache: bool,
+
+    /// Only download the cards in this file (one id, GA slug or card name per line)
+    #[arg(long, value_name = "FILE")]
+    card_list: Option<PathBuf>,
+
+    /// Cache downloaded source images in this directory and reuse them across datasets
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Hard-link processed images that already exist in --reference-dir instead of downloading them
+    #[arg(long, default_value_t = false, requires = "reference_dir")]
+    hardlink_existing: bool,
+
+    /// Dataset (a --path of an earlier fetch) to hard-link existing images from
+    #[arg(long, value_name = "DIR", requires = "hardlink_existing")]
+    reference_dir: Option<PathBuf>,
+
+    /// Ignore --cache-dir (e.g. one set in a config file) and always download
+    #[arg(long, default_value_t = false)]
+    no_cache: bool,
+
+    /// Write downloads here until they are validated, e.g. fast local storage
+    #[arg(long, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// POST the final summary as JSON to this URL when the run finishes
+    #[arg(long)]
+    notify_url: Option<reqwest::Url>,
+
+    /// Print the final summary as one line of JSON instead of the human-readable report
+    #[arg(long, default_value_t = false)]
+    output_json: bool,
+}
+
+/// Arguments of the `augment` command
+#[derive(clap::Args, Debug)]
+struct AugmentArgs {
+    /// Path to the dataset directory (should have train/ subdir)
+    #[arg(short, long, required_unless_present = "preview")]
+    path: Option<String>,
+
+    /// Number of augmented versions to generate per image
+    #[arg(short, long, default_value_t = 5)]
+    amount: u32,
+
+    /// Number of threads to use for augmentation (defaults to number of CPU cores, 0 also means all cores)
+    #[arg(short, long, default_value_t = thread::available_parallelism().map_or(1, |p| p.get()))]
+    threads: usize,
+
+    /// Verify image integrity after augmentation
+    #[arg(long, default_value_t = false)]
+    verify: bool,
+
+    /// File name template for augmented images ({index}, {index:N}, {card}, {orig})
+    #[arg(long, default_value = augmentation::DEFAULT_NAMING_TEMPLATE)]
+    naming_template: String,
+
+    /// Image format for augmented outputs (jpeg is lossy; use png or preserve for PNG datasets)
+    #[arg(long, value_enum, default_value_t = augmentation::AugmentOutputFormat::Jpeg)]
+    output_format: augmentation::AugmentOutputFormat,
+
+    /// Augmentations to choose from, comma separated (defaults to all)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    augmentations: Vec<augmentation::AugmentationType>,
+
+    /// Color used to fill areas exposed by rotation, perspective and shear (r,g,b or #rrggbb)
+    #[arg(long, default_value = "0,0,0", value_parser = augmentation::parse_fill_color)]
+    fill_color: image::Rgb<u8>,
+
+    /// Override an augmentation's intensity range as <type>=<min>:<max>, may be repeated
+    #[arg(long, value_parser = augmentation::parse_intensity)]
+    intensity: Vec<(augmentation::AugmentationType, augmentation::IntensityRange)>,
+
+    /// POST the final summary as JSON to this URL when the run finishes
+    #[arg(long)]
+    notify_url: Option<reqwest::Url>,
+
+    /// Draw the applied augmentations onto each augmented image, for debugging
+    #[arg(long, default_value_t = false)]
+    label: bool,
+
+    /// Corner of the image the label is drawn in
+    #[arg(long, value_enum, default_value_t = augmentation::LabelPosition::BottomLeft, requires = "label")]
+    label_position: augmentation::LabelPosition,
+
+    /// Label text color as r,g,b or #rrggbb
+    #[arg(long, default_value = "255,255,0", value_parser = augmentation::parse_fill_color, requires = "label")]
+    label_color: image::Rgb<u8>,
+
+    /// Augment card directories again even when their .augmented marker shows they are done
+    #[arg(long, default_value_t = false)]
+    force: bool,
+
+    /// Write one example of each augmentation applied to this image instead of augmenting a dataset
+    #[arg(long, conflicts_with = "path")]
+    preview: Option<PathBuf>,
+
+    /// Directory where preview images are written
+    #[arg(long, default_value = "augmentation-preview", requires = "preview")]
+    preview_dir: PathBuf,
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Fetch trading card game data from various APIs
-    Fetch {
-        /// Trading card game type to fetch data for
-        #[arg(value_enum)]
-        tcg: TcgType,
-        /// Path where to save the data
+    #[command(args_override_self = true)]
+    Fetch(Box<FetchArgs>),
+    /// Remove temp files and empty card directories left by failed runs
+    #[command(args_override_self = true)]
+    Clean {
+        /// Path where the data was saved
         #[arg(short, long, default_value = "tcg-data")]
         path: String,
 
-        /// Amount of cards to fetch
-        #[arg(short, long, default_value = "all")]
-        amount: Option<String>,
+        /// Actually delete the files (default is a dry run)
+        #[arg(long, default_value_t = false)]
+        apply: bool,
+    },
+    /// Compute per-channel mean and standard deviation of the card images
+    #[command(args_override_self = true)]
+    Stats {
+        /// Path where the data was saved
+        #[arg(short, long, default_value = "tcg-data")]
+        path: String,
 
-        /// Number of threads to use for downloading images (defaults to number of CPU cores)
-        #[arg(short, long, default_value_t = thread::available_parallelism().map_or(1, |p| p.get()))]
-        threads: usize,
+        /// Card directories are grouped by set code, as written by fetch --group-by-set
+        #[arg(long, default_value_t = false)]
+        group_by_set: bool,
 
-        /// Width for processed images
-        #[arg(long, default_value_t = 500)]
-        width: u32,
+        /// File to write the statistics to (defaults to <path>/dataset_stats.json)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Check that the output path, network access and image decoding work
+    #[command(args_override_self = true)]
+    Doctor {
+        /// TCG whose API to check (defaults to all built-in ones)
+        #[arg(value_enum)]
+        tcg: Option<TcgType>,
 
-        /// Height for processed images
-        #[arg(long, default_value_t = 700)]
-        height: u32,
+        /// Path where the data will be saved
+        #[arg(short, long, default_value = "tcg-data")]
+        path: String,
     },
+    /// List the supported trading card games and where their data comes from
+    ListTcgs,
     /// Generate augmented versions of TCG card images
-    Augment {
-        /// Path to the dataset directory (should have train/ subdir)
-        #[arg(short, long)]
-        path: String,
+    #[command(args_override_self = true)]
+    Augment(Box<AugmentArgs>),
+    /// Print a shell completion script
+    #[command(hide = true)]
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
 
-        /// Number of augmented versions to generate per image
-        #[arg(short, long, default_value_t = 5)]
-        amount: u32,
+/// Parse the command line, filling in option values from `--config` when given
+fn parse_args() -> Args {
+    let mut argv: Vec<OsString> = std::env::args_os().collect();
 
-        /// Verify image integrity after augmentation
-        #[arg(long, default_value_t = false)]
-        verify: bool,
-    },
+    // Locate --config and the subcommand before clap runs so file values can be spliced in
+    let mut config = None;
+    let mut subcommand = None;
+    let mut index = 1;
+    while index < argv.len() {
+        let arg = argv[index].to_string_lossy();
+        if arg == "--config" {
+            config = argv.get(index + 1).map(PathBuf::from);
+            index += 2;
+            continue;
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            config = Some(PathBuf::from(path));
+        } else if subcommand.is_none() && !arg.starts_with('-') {
+            subcommand = Some((index, arg.into_owned()));
+        }
+        index += 1;
+    }
+
+    if let (Some(config), Some((position, name))) = (config, subcommand) {
+        match utils::config::config_args(&config, &Args::command(), &name) {
+            Ok(file_args) => {
+                argv.splice(position + 1..position + 1, file_args);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    Args::parse_from(argv)
+}
+
+/// Ask the user to confirm a destructive operation on stdin
+fn confirm_on_stdin(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Send the completion notification, logging rather than failing on errors
+async fn notify(client: &reqwest::Client, url: &reqwest::Url, payload: &serde_json::Value) {
+    match utils::http::post_notification(client, url.as_str(), payload).await {
+        Ok(()) => println!("Sent completion notification to {}", url),
+        Err(e) => eprintln!("Error sending completion notification: {}", e),
+    }
 }
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    let args = Args::parse();
+    let args = parse_args();
 
     match args.command {
-        Commands::Fetch {
-            tcg,
-            path,
-            amount,
-            threads,
-            width,
-            height,
-        } => {
-            println!("TCG: {:?}", tcg);
+        Commands::Fetch(fetch_args) => {
+            let FetchArgs {
+                tcg,
+                path,
+                staging_dir,
+                source,
+                amount,
+                max_total,
+                scryfall_dataset,
+                editions_per_card,
+                pretty_json,
+                min_date,
+                source_url,
+                records_path,
+                id_path,
+                image_path,
+                name_path,
+                max_age,
+                refresh_json,
+                threads,
+                adaptive_concurrency,
+                stall_timeout,
+                per_host,
+                limit_rate_per_host,
+                checkpoint_interval,
+                batch_size,
+                schedule,
+                max_memory,
+                require_https,
+                image_mirror,
+                header,
+                pool_size,
+                http2,
+                max_redirects,
+                width,
+                height,
+                mut sizes,
+                max_image_dimension,
+                image_format,
+                channels,
+                strip_metadata,
+                auto_contrast,
+                sharpen,
+                auto_crop,
+                convert_srgb,
+                group_by_set,
+                only_new_sets,
+                dir_naming,
+                json_only,
+                dry_validate,
+                convert_existing,
+                preview,
+                slowest,
+                verify_after,
+                retry_failed_only,
+                sort,
+                stable_order,
+                sample_per_set,
+                include_backs,
+                include_tokens,
+                exclude_digital,
+                exclude_promos,
+                include_promos,
+                finish,
+                min_image_status,
+                dedupe_by_oracle_id,
+                delete_extraneous,
+                trash,
+                yes,
+                hash_index,
+                manifest,
+                manifest_format,
+                output_template,
+                parse_cache,
+                card_list,
+                cache_dir,
+                hardlink_existing,
+                reference_dir,
+                no_cache,
+                temp_dir,
+                notify_url,
+                output_json,
+            } = *fetch_args;
+            let tcgs = TcgChoice::resolve(&tcg);
+            println!("TCG: {:?}", tcgs);
+
+            // clap makes --source-url require both paths
+            let custom = match (source_url, id_path, image_path) {
+                (Some(url), Some(id_path), Some(image_path)) => Some(CustomSource {
+                    url,
+                    records_path,
+                    id_path,
+                    image_path,
+                    name_path,
+                }),
+                _ => None,
+            };
+            if custom.is_some() && !tcgs.contains(&TcgType::Custom) {
+                eprintln!("Warning: --source-url only applies to the custom TCG and is ignored");
+            }
             println!("Path: {}", path);
+
+            // Images go to the bucket; the JSON and run state stay in the staging directory
+            let (path, image_store) = if path.starts_with("s3://") {
+                let store: Arc<dyn ImageStore> = Arc::new(S3Store::new(&path)?);
+                println!("Staging in: {}", staging_dir);
+                (staging_dir, Some(store))
+            } else {
+                (path, None)
+            };
             println!("Fetching data of type: All");
 
-            // Ensure the output directory exists
-            utils::files::ensure_directories(&path)?;
+            // Asking for a size twice would only write the same file twice
+            let mut seen_sizes = HashSet::new();
+            sizes.retain(|size| seen_sizes.insert(*size));
+            if !sizes.is_empty() {
+                let listed: Vec<String> = sizes
+                    .iter()
+                    .map(|(width, height)| format!("{}x{}", width, height))
+                    .collect();
+                println!("Output sizes: {}", listed.join(", "));
+            }
+
+            if channels == Channels::Rgba && image_format == OutputFormat::Jpeg {
+                eprintln!("Warning: JPEG has no alpha channel, images will be saved as RGB");
+            }
 
-            // Fetch and download JSON file for the selected data type
-            let fetch_result = match tcg {
-                TcgType::Mtg => tcg::mtg::fetch_mtg_bulk_data(&path).await,
-                TcgType::Ga => tcg::ga::fetch_ga_all_cards(&path).await,
+            let client = utils::http::build_client(&HttpOptions {
+                headers: header,
+                pool_size,
+                http2,
+                max_redirects: Some(max_redirects),
+            })?;
+            let options = FetchOptions {
+                tcgs,
+                path,
+                source,
+                scryfall_dataset,
+                editions_per_card: editions_per_card.map(NonZeroUsize::get),
+                pretty_json,
+                min_date,
+                custom,
+                max_age,
+                refresh_json,
+                amount,
+                max_total,
+                threads,
+                adaptive_concurrency,
+                stall_timeout: (!stall_timeout.is_zero()).then_some(stall_timeout),
+                per_host: per_host.map(NonZeroUsize::get),
+                limit_rate_per_host,
+                schedule,
+                max_memory,
+                image_mirror,
+                client: client.clone(),
+                processing: ImageProcessing {
+                    width,
+                    height,
+                    format: image_format,
+                    channels,
+                    max_dimension: max_image_dimension,
+                    strip_metadata,
+                    auto_contrast,
+                    sharpen,
+                    auto_crop,
+                    sizes,
+                    convert_srgb,
+                },
+                group_by_set,
+                json_only,
+                dry_validate,
+                convert_existing,
+                preview,
+                slowest,
+                verify_after,
+                retry_failed_only,
+                checkpoint_interval: checkpoint_interval.map(NonZeroUsize::get),
+                batch_size: batch_size.map(NonZeroUsize::get),
+                sort,
+                stable_order,
+                sample_per_set: sample_per_set.map(NonZeroUsize::get),
+                filter: CardFilter {
+                    include_backs,
+                    include_tokens,
+                    exclude_digital,
+                    exclude_promos: exclude_promos && !include_promos,
+                    finish,
+                    min_image_status,
+                    dedupe_by_oracle_id,
+                },
+                delete_extraneous,
+                trash,
+                yes,
+                confirm: Some(Confirm::new(confirm_on_stdin)),
+                hash_index: hash_index.map(PathBuf::from),
+                manifest: manifest.map(PathBuf::from),
+                manifest_format,
+                parse_cache,
+                require_https,
+                only_new_sets,
+                card_list,
+                cache_dir: if no_cache { None } else { cache_dir },
+                reference_dir: reference_dir.filter(|_| hardlink_existing),
+                temp_dir,
+                dir_naming,
+                output_template,
+                image_store,
             };
 
-            match fetch_result {
-                Ok(files) => {
-                    println!("\nDownloaded JSON files:");
-                    for file in &files {
-                        println!("  - {}", file);
-                    }
+            let result = tcg_fetch::fetch(&options).await;
 
-                    let mut total_skipped_existing = 0;
-                    let mut total_skipped_soon = 0;
-                    for file in files {
-                        println!("\nProcessing file: {}", file);
-                        match utils::images::download_card_images(
-                            &file,
-                            &path,
-                            amount.as_deref(),
-                            threads,
-                            width,
-                            height,
-                            &tcg,
-                        )
-                        .await
-                        {
-                            Ok((skipped_existing, skipped_soon)) => {
-                                total_skipped_existing += skipped_existing;
-                                total_skipped_soon += skipped_soon;
-                            }
-                            Err(e) => eprintln!("Error downloading images: {}", e),
-                        }
+            if let Some(url) = &notify_url {
+                let payload = match &result {
+                    Ok(summary) => {
+                        json!({ "command": "fetch", "success": true, "summary": summary })
                     }
-
-                    if total_skipped_existing > 0 || total_skipped_soon > 0 {
-                        println!();
-                        if total_skipped_existing > 0 {
-                            println!("Skipped {} cards (already existed)", total_skipped_existing);
-                        }
-                        if total_skipped_soon > 0 {
-                            println!(
-                                "Skipped {} cards (soon.jpg placeholder images)",
-                                total_skipped_soon
-                            );
-                        }
+                    Err(e) => {
+                        json!({ "command": "fetch", "success": false, "error": e.to_string() })
                     }
+                };
+                notify(&client, url, &payload).await;
+            }
 
-                    // Count and display the number of directories in train folder
-                    if let Err(e) = utils::files::count_train_directories(&path) {
-                        eprintln!("Error counting train directories: {}", e);
+            match result {
+                Ok(summary) if output_json => println!(
+                    "{}",
+                    json!({
+                        "success": true,
+                        "files": summary.files,
+                        "downloaded": summary.downloaded,
+                        "skipped_existing": summary.skipped_existing,
+                        "skipped_soon": summary.skipped_soon,
+                        "failed": summary.failed.len(),
+                        "redirect_failures": summary.redirect_failures,
+                        "mirrored": summary.mirrored,
+                        "linked": summary.linked,
+                        "insecure_rejected": summary.insecure_rejected,
+                        "https_upgraded": summary.https_upgraded,
+                        "auto_cropped": summary.auto_cropped,
+                        "converted": summary.converted,
+                        "timing": summary.timing,
+                    })
+                ),
+                Ok(summary) => summary.print_report(),
+                Err(e) => {
+                    if output_json {
+                        println!("{}", json!({ "success": false, "error": e.to_string() }));
                     }
+                    eprintln!("{}", e);
+                    std::process::exit(1);
                 }
-                Err(e) => {
-                    eprintln!("Error fetching bulk data: {}", e);
+            }
+        }
+        Commands::Completions { shell } => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+        }
+        Commands::Doctor { tcg, path } => {
+            let client = utils::http::build_client(&HttpOptions::default())?;
+            let tcgs = match tcg {
+                Some(tcg) => vec![tcg],
+                None => built_in_tcgs(),
+            };
+
+            let checks = tcg_fetch::doctor::run_checks(&client, &path, &tcgs).await;
+            for check in &checks {
+                match &check.result {
+                    Ok(detail) => println!("[PASS] {}: {}", check.name, detail),
+                    Err(detail) => println!("[FAIL] {}: {}", check.name, detail),
                 }
             }
+
+            let failed = checks.iter().filter(|check| check.result.is_err()).count();
+            if failed > 0 {
+                eprintln!("{} of {} checks failed", failed, checks.len());
+                std::process::exit(1);
+            }
+            println!("All {} checks passed", checks.len());
         }
-        Commands::Augment {
+        Commands::ListTcgs => {
+            println!("Supported TCGs:");
+            for tcg in TcgType::value_variants() {
+                let source = tcg.source();
+                let name = tcg
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string())
+                    .unwrap_or_default();
+                println!("\n  {:<8}{}", name, source.name());
+                println!("          Source:             {}", source.api_url());
+                println!(
+                    "          Card JSON:          {}.json",
+                    source.json_file_stem()
+                );
+                println!(
+                    "          Default image size: {}x{}",
+                    utils::images::DEFAULT_WIDTH,
+                    utils::images::DEFAULT_HEIGHT
+                );
+            }
+        }
+        Commands::Clean { path, apply } => {
+            let stats = utils::files::clean_train_directory(&path, apply)?;
+            let verb = if apply { "Removed" } else { "Would remove" };
+            println!(
+                "{} {} temp files and {} empty directories",
+                verb, stats.temp_files, stats.empty_dirs
+            );
+            if !apply && (stats.temp_files > 0 || stats.empty_dirs > 0) {
+                println!("Dry run only, pass --apply to delete");
+            }
+        }
+        Commands::Stats {
             path,
-            amount,
-            verify,
+            group_by_set,
+            output,
         } => {
-            let augmentation_args = augmentation::AugmentationArgs {
+            let stats = utils::images::compute_channel_stats(&path, group_by_set)?;
+            let output = output.unwrap_or_else(|| PathBuf::from(&path).join("dataset_stats.json"));
+            let json = serde_json::to_string_pretty(&stats).map_err(io::Error::other)?;
+            std::fs::write(&output, json + "\n")?;
+
+            println!(
+                "mean: [{:.4}, {:.4}, {:.4}]",
+                stats.mean[0], stats.mean[1], stats.mean[2]
+            );
+            println!(
+                "std:  [{:.4}, {:.4}, {:.4}]",
+                stats.std[0], stats.std[1], stats.std[2]
+            );
+            if stats.failed > 0 {
+                eprintln!("{} images could not be read and were skipped", stats.failed);
+            }
+            println!(
+                "Statistics for {} images written to {}",
+                stats.images,
+                output.display()
+            );
+        }
+        Commands::Augment(augment_args) => {
+            let AugmentArgs {
                 path,
                 amount,
+                threads,
+                verify,
+                naming_template,
+                output_format,
+                augmentations,
+                fill_color,
+                intensity,
+                notify_url,
+                label,
+                label_position,
+                label_color,
+                force,
+                preview,
+                preview_dir,
+            } = *augment_args;
+            let mut intensities = augmentation::AugmentationIntensities::default();
+            for (aug_type, range) in intensity {
+                intensities.set(aug_type, range);
+            }
+
+            if let Some(image) = preview {
+                match augmentation::preview_augmentations(
+                    &image,
+                    &preview_dir,
+                    &augmentations,
+                    output_format,
+                    fill_color,
+                    &intensities,
+                ) {
+                    Ok(outputs) => println!(
+                        "Wrote {} preview images to {}",
+                        outputs.len(),
+                        preview_dir.display()
+                    ),
+                    Err(e) => {
+                        eprintln!("Error generating augmentation preview: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return Ok(());
+            }
+
+            let options = AugmentOptions {
+                // clap requires --path whenever --preview is absent
+                path: path.unwrap_or_default(),
+                amount,
                 verify,
+                naming_template,
+                output_format,
+                augmentations,
+                fill_color,
+                intensities,
+                threads,
+                label: label.then_some(augmentation::LabelStyle {
+                    position: label_position,
+                    color: label_color,
+                }),
+                force,
             };
 
-            if let Err(e) = augmentation::augment_dataset(augmentation_args).await {
+            let path = options.path.clone();
+            let result = tcg_fetch::augment(options).await;
+
+            if let Some(url) = &notify_url {
+                let payload = match &result {
+                    Ok(()) => json!({ "command": "augment", "success": true, "path": path }),
+                    Err(e) => json!({
+                        "command": "augment",
+                        "success": false,
+                        "path": path,
+                        "error": e.to_string(),
+                    }),
+                };
+                let client = utils::http::build_client(&HttpOptions::default())?;
+                notify(&client, url, &payload).await;
+            }
+
+            if let Err(e) = result {
                 eprintln!("Error during augmentation: {}", e);
                 std::process::exit(1);
             }
@@ -0,0 +1,259 @@
+use crate::tcg::{MetadataOptions, TcgSource, TcgType, UnifiedCard};
+use crate::utils::files::check_json_files;
+use crate::utils::images::{CardFilter, LoadedCards};
+use crate::utils::json_path::JsonPath;
+use crate::utils::json_shape::describe;
+use crate::utils::template::sanitize_component;
+use futures::future::BoxFuture;
+use reqwest;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+/// Extraction failures listed by name before the rest are only counted
+const LISTED_FAILURES: usize = 10;
+
+/// A card API without dedicated support, described on the command line
+///
+/// The document at `url` is fetched once and every record in it is turned into
+/// a card by the path expressions, see [`JsonPath`].
+#[derive(Debug, Clone)]
+pub struct CustomSource {
+    /// JSON document listing the cards
+    pub url: reqwest::Url,
+    /// Where the list of card records is in the document
+    pub records_path: JsonPath,
+    /// Card id in each record
+    pub id_path: JsonPath,
+    /// Image URL in each record, relative URLs are resolved against `url`
+    pub image_path: JsonPath,
+    /// Card name in each record, if the API has one
+    pub name_path: Option<JsonPath>,
+}
+
+/// Cards read from a custom source and the records that could not be read
+#[derive(Debug, Default)]
+pub struct ExtractedCards {
+    pub cards: Vec<UnifiedCard>,
+    /// Why each skipped record was skipped, prefixed with its position
+    pub failures: Vec<String>,
+}
+
+impl CustomSource {
+    /// Turn the records in `document` into cards
+    ///
+    /// Records the paths don't match are reported in `failures`; it is an error
+    /// when the records can't be found or not a single one matches.
+    pub fn extract(&self, document: &Value) -> io::Result<ExtractedCards> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let records = self.records_path.resolve(document).map_err(|e| {
+            invalid(format!(
+                "Cannot find the card records in {}: {}",
+                self.url, e
+            ))
+        })?;
+        let Value::Array(records) = records else {
+            return Err(invalid(format!(
+                "--records-path '{}' selects {} in {}, expected an array of cards",
+                self.records_path,
+                describe(records),
+                self.url
+            )));
+        };
+
+        let mut extracted = ExtractedCards::default();
+        let mut seen_ids = HashSet::new();
+        for (index, record) in records.iter().enumerate() {
+            match self.extract_card(record) {
+                Ok(card) if !seen_ids.insert(card.id.clone()) => extracted
+                    .failures
+                    .push(format!("record {}: duplicate id '{}'", index, card.id)),
+                Ok(card) => extracted.cards.push(card),
+                Err(reason) => extracted
+                    .failures
+                    .push(format!("record {}: {}", index, reason)),
+            }
+        }
+
+        if extracted.cards.is_empty() && !extracted.failures.is_empty() {
+            return Err(invalid(format!(
+                "None of the {} records in {} could be read, check --id-path and --image-path. First failure: {}",
+                records.len(),
+                self.url,
+                extracted.failures[0]
+            )));
+        }
+
+        Ok(extracted)
+    }
+
+    fn extract_card(&self, record: &Value) -> Result<UnifiedCard, String> {
+        let id = match self
+            .id_path
+            .resolve(record)
+            .map_err(|e| format!("--id-path {}", e))?
+        {
+            Value::String(id) if !id.trim().is_empty() => id.trim().to_string(),
+            Value::Number(id) => id.to_string(),
+            other => {
+                return Err(format!(
+                    "--id-path '{}' selects {}, expected a non-empty string or a number",
+                    self.id_path,
+                    describe(other)
+                ))
+            }
+        };
+
+        let image = match self
+            .image_path
+            .resolve(record)
+            .map_err(|e| format!("--image-path {}", e))?
+        {
+            Value::String(image) if !image.trim().is_empty() => image.trim(),
+            other => {
+                return Err(format!(
+                    "--image-path '{}' selects {}, expected a URL string",
+                    self.image_path,
+                    describe(other)
+                ))
+            }
+        };
+        let image_url = self
+            .url
+            .join(image)
+            .map_err(|e| format!("invalid image URL '{}': {}", image, e))?;
+
+        let name = self
+            .name_path
+            .as_ref()
+            .and_then(|path| path.resolve(record).ok())
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        Ok(UnifiedCard {
+            // Ids become directory names, so keep them to one safe path component
+            id: sanitize_component(Some(&id)),
+            image_url: image_url.to_string(),
+            name,
+            set: None,
+            rarity: None,
+            collector_number: None,
+            lang: None,
+            dir_name: None,
+        })
+    }
+}
+
+pub struct CustomApi;
+
+impl TcgSource for CustomApi {
+    fn name(&self) -> &'static str {
+        "Custom source"
+    }
+
+    fn api_url(&self) -> &'static str {
+        "given with --source-url"
+    }
+
+    fn json_file_stem(&self) -> &'static str {
+        "custom_cards"
+    }
+
+    fn temp_extension(&self) -> &'static str {
+        // The real format is detected from the contents when the image is processed
+        "img"
+    }
+
+    fn has_sets(&self) -> bool {
+        false
+    }
+
+    fn fetch_metadata<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        directory: &'a str,
+        options: &'a MetadataOptions,
+    ) -> BoxFuture<'a, io::Result<Vec<String>>> {
+        Box::pin(fetch_custom_cards(client, directory, options))
+    }
+
+    fn parse_cards(&self, json: &str, _filter: &CardFilter) -> io::Result<LoadedCards> {
+        // custom_cards.json already holds unified cards, extracted when it was built
+        Ok(LoadedCards {
+            cards: serde_json::from_str(json)?,
+            ..Default::default()
+        })
+    }
+}
+
+/// Build `custom_cards.json` from the document at `--source-url`
+async fn fetch_custom_cards(
+    client: &reqwest::Client,
+    directory: &str,
+    options: &MetadataOptions,
+) -> io::Result<Vec<String>> {
+    let existing_files = check_json_files(directory, &TcgType::Custom, options.max_age);
+    if !existing_files.is_empty() {
+        println!("Using existing JSON files");
+        if options.custom.is_some() {
+            println!("Note: --source-url and the path options apply when custom_cards.json is built; pass --refresh-json to rebuild it");
+        }
+        return Ok(existing_files);
+    }
+
+    let Some(source) = &options.custom else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The custom TCG needs --source-url, --id-path and --image-path",
+        ));
+    };
+
+    println!("Fetching custom card data from {}...", source.url);
+    let response = client
+        .get(source.url.clone())
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .map_err(|e| io::Error::other(format!("Request error: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(io::Error::other(format!(
+            "HTTP {} for URL: {}",
+            response.status(),
+            source.url
+        )));
+    }
+    let document: Value = response.json().await.map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse JSON from {}: {}", source.url, e),
+        )
+    })?;
+
+    let extracted = source.extract(&document)?;
+    let file = Path::new(directory).join("custom_cards.json");
+    let json_data = serde_json::to_string(&extracted.cards)
+        .map_err(|e| io::Error::other(format!("Failed to serialize JSON: {}", e)))?;
+    std::fs::write(&file, json_data)?;
+    println!("Successfully downloaded: {}", file.display());
+
+    println!("Extracted {} cards", extracted.cards.len());
+    if !extracted.failures.is_empty() {
+        println!(
+            "Skipped {} records that could not be read:",
+            extracted.failures.len()
+        );
+        for failure in extracted.failures.iter().take(LISTED_FAILURES) {
+            println!("  - {}", failure);
+        }
+        if extracted.failures.len() > LISTED_FAILURES {
+            println!(
+                "  ... and {} more",
+                extracted.failures.len() - LISTED_FAILURES
+            );
+        }
+    }
+
+    Ok(vec![file.to_string_lossy().into_owned()])
+}
@@ -1,12 +1,27 @@
-use crate::tcg::TcgType;
+use crate::tcg::{MetadataOptions, TcgSource, TcgType, UnifiedCard};
 use crate::utils::files::check_json_files;
-use crate::utils::http::get_user_agent;
+use crate::utils::images::{CardFilter, LoadedCards};
+use crate::utils::json_shape::JsonShape;
+use chrono::{DateTime, NaiveDate};
+use futures::future::BoxFuture;
 use futures::stream::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
 use reqwest;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json;
-use std::io;
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
 use std::path::Path;
+use std::time::Duration;
+
+/// The `/cards/all` list of card names and slugs
+const CARD_LIST_SHAPE: JsonShape = JsonShape {
+    source: "Grand Archive card list",
+    records_at: &[],
+    keyed_records: false,
+    record_fields: &["name", "slug"],
+};
 
 #[derive(Debug, Deserialize)]
 pub struct GaCard {
@@ -17,113 +32,537 @@ pub struct GaCard {
 
 #[derive(Debug, Deserialize)]
 pub struct GaCardDetail {
-    #[allow(dead_code)]
     pub name: String,
+    #[serde(default)]
     pub editions: Vec<GaEdition>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GaEdition {
     pub slug: String,
-    pub image: String,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub rarity: Option<u32>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// Whether an edition image looks like an API-relative path (e.g. `/cards/images/foo.jpg`)
+fn is_valid_image_path(image: &str) -> bool {
+    image.len() > 1
+        && image.starts_with('/')
+        && !image.starts_with("//")
+        && !image.contains(char::is_whitespace)
+}
+
+/// Card details resolved so far, one JSON line per card, while building `ga_cards.json`
+const GA_PARTIAL_FILE: &str = "ga_cards.partial.jsonl";
+
+/// Attempts made at downloading the `/cards/all` list before giving up
+const CARD_LIST_ATTEMPTS: u32 = 3;
+
+/// Base wait between card list attempts, multiplied by the attempt number
+const CARD_LIST_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How long the card list download may go without receiving any data
+const CARD_LIST_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Processed editions of one card, persisted to the partial file
+#[derive(Debug, Serialize, Deserialize)]
+struct GaCardProgress {
+    card: String,
+    name: String,
+    entries: Vec<serde_json::Value>,
+    no_editions: bool,
+    invalid_images: Vec<String>,
+}
+
+impl GaCardProgress {
+    /// Build the `ga_cards.json` entries for a card, skipping editions without a usable image
+    fn from_detail(card: String, detail: GaCardDetail) -> Self {
+        let no_editions = detail.editions.is_empty();
+        let mut entries = Vec::new();
+        let mut invalid_images = Vec::new();
+
+        for edition in detail.editions {
+            match edition.image.as_deref() {
+                Some(image) if is_valid_image_path(image) => entries.push(serde_json::json!({
+                    "slug": edition.slug,
+                    "name": detail.name,
+                    "rarity": edition.rarity,
+                    "created_at": edition.created_at,
+                    "image": format!("https://api.gatcg.com{}", image)
+                })),
+                _ => invalid_images.push(edition.slug),
+            }
+        }
+
+        Self {
+            card,
+            name: detail.name,
+            entries,
+            no_editions,
+            invalid_images,
+        }
+    }
+}
+
+/// Whether a `ga_cards.json` entry was created on or after `min_date`
+///
+/// Entries without a parseable `created_at` are kept, since their age is unknown.
+fn created_on_or_after(entry: &serde_json::Value, min_date: NaiveDate) -> bool {
+    entry["created_at"]
+        .as_str()
+        .and_then(|created_at| DateTime::parse_from_rfc3339(created_at).ok())
+        .is_none_or(|created_at| created_at.date_naive() >= min_date)
+}
+
+/// Load the cards completed by an interrupted run, ignoring a truncated final line
+fn load_partial_progress(path: &Path) -> io::Result<Vec<GaCardProgress>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    if !content.is_empty() && !content.ends_with('\n') {
+        // Terminate the truncated line so new records start on their own line
+        OpenOptions::new()
+            .append(true)
+            .open(path)?
+            .write_all(b"\n")?;
+    }
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
 }
 
 pub struct GaApi;
 
 impl GaApi {
-    fn get_api_url() -> &'static str {
+    pub(crate) fn get_api_url() -> &'static str {
         "https://api.gatcg.com/cards/all"
     }
 }
 
+impl TcgSource for GaApi {
+    fn name(&self) -> &'static str {
+        "Grand Archive"
+    }
+
+    fn api_url(&self) -> &'static str {
+        Self::get_api_url()
+    }
+
+    fn json_file_stem(&self) -> &'static str {
+        "ga_cards"
+    }
+
+    fn temp_extension(&self) -> &'static str {
+        "jpg"
+    }
+
+    fn has_sets(&self) -> bool {
+        false
+    }
+
+    fn fetch_metadata<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        directory: &'a str,
+        options: &'a MetadataOptions,
+    ) -> BoxFuture<'a, io::Result<Vec<String>>> {
+        Box::pin(fetch_ga_all_cards(
+            client,
+            directory,
+            options.editions_per_card,
+            options.min_date,
+            options.max_age,
+            options.pretty_json,
+        ))
+    }
+
+    fn parse_cards(&self, json: &str, _filter: &CardFilter) -> io::Result<LoadedCards> {
+        let mut loaded = LoadedCards::default();
+        let ga_cards: Vec<serde_json::Value> = serde_json::from_str(json)?;
+        let mut cards = Vec::with_capacity(ga_cards.len());
+        for card in ga_cards {
+            let id = card["slug"].as_str().unwrap_or("unknown").to_string();
+            let image_url = card["image"].as_str().unwrap_or("").to_string();
+            if image_url.is_empty() {
+                loaded.imageless.push(id);
+                continue;
+            }
+            cards.push(UnifiedCard {
+                id,
+                image_url,
+                name: card["name"].as_str().map(str::to_string),
+                set: None,
+                rarity: card["rarity"].as_u64().map(|rarity| rarity.to_string()),
+                collector_number: None,
+                lang: None,
+                dir_name: None,
+            });
+        }
+        loaded.cards = cards;
+        Ok(loaded)
+    }
+}
+
 async fn fetch_ga_card_detail(client: &reqwest::Client, slug: &str) -> io::Result<GaCardDetail> {
     let url = format!("https://api.gatcg.com/cards/{}", slug);
     let response = client
         .get(&url)
-        .header("User-Agent", get_user_agent())
         .header("Accept", "application/json")
         .send()
         .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Request error: {}", e)))?;
+        .map_err(|e| io::Error::other(format!("Request error: {}", e)))?;
 
-    let card_detail: GaCardDetail = response.json().await.map_err(|e| {
-        io::Error::new(io::ErrorKind::Other, format!("Failed to parse JSON: {}", e))
-    })?;
+    let card_detail: GaCardDetail = response
+        .json()
+        .await
+        .map_err(|e| io::Error::other(format!("Failed to parse JSON: {}", e)))?;
 
     Ok(card_detail)
 }
 
-pub async fn fetch_ga_all_cards(directory: &str) -> io::Result<Vec<String>> {
+/// Download the `/cards/all` list, retrying when the connection fails or stalls
+///
+/// The list is one large response, so its bytes are shown on a progress bar (a
+/// spinner when the server sends no length) instead of leaving the run silent.
+async fn fetch_ga_card_list(client: &reqwest::Client) -> io::Result<Vec<GaCard>> {
+    let url = GaApi::get_api_url();
+    let mut attempt = 1;
+
+    loop {
+        match download_ga_card_list(client, url).await {
+            Ok(body) => {
+                let parse_error =
+                    |e: serde_json::Error| io::Error::other(format!("Failed to parse JSON: {}", e));
+                let document: serde_json::Value =
+                    serde_json::from_slice(&body).map_err(parse_error)?;
+                CARD_LIST_SHAPE.check(&document)?;
+                return serde_json::from_value(document).map_err(parse_error);
+            }
+            Err(e) if attempt < CARD_LIST_ATTEMPTS => {
+                let wait = CARD_LIST_RETRY_DELAY * attempt;
+                println!(
+                    "Card list download failed ({}), retrying in {}s ({}/{})",
+                    e,
+                    wait.as_secs(),
+                    attempt,
+                    CARD_LIST_ATTEMPTS - 1
+                );
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// One attempt at downloading the card list body
+async fn download_ga_card_list(client: &reqwest::Client, url: &str) -> io::Result<Vec<u8>> {
+    let stalled = || {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "no data received for {}s",
+                CARD_LIST_STALL_TIMEOUT.as_secs()
+            ),
+        )
+    };
+
+    let mut response = tokio::time::timeout(
+        CARD_LIST_STALL_TIMEOUT,
+        client.get(url).header("Accept", "application/json").send(),
+    )
+    .await
+    .map_err(|_| stalled())?
+    .map_err(|e| io::Error::other(format!("Request error: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(io::Error::other(format!(
+            "HTTP {} for URL: {}",
+            response.status(),
+            url
+        )));
+    }
+
+    let pb = match response.content_length() {
+        Some(len) => {
+            let pb = ProgressBar::new(len);
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta}) {msg}")
+                    .unwrap()
+                    .progress_chars("#>-"),
+            );
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template(
+                        "{spinner:.green} [{elapsed_precise}] {bytes} ({bytes_per_sec}) {msg}",
+                    )
+                    .unwrap(),
+            );
+            pb
+        }
+    };
+    pb.set_message("card list");
+
+    let mut body = Vec::with_capacity(response.content_length().unwrap_or(0) as usize);
+    loop {
+        let chunk = tokio::time::timeout(CARD_LIST_STALL_TIMEOUT, response.chunk())
+            .await
+            .map_err(|_| stalled())
+            .and_then(|chunk| {
+                chunk.map_err(|e| io::Error::other(format!("Download interrupted: {}", e)))
+            });
+        match chunk {
+            Ok(Some(chunk)) => {
+                body.extend_from_slice(&chunk);
+                pb.inc(chunk.len() as u64);
+            }
+            Ok(None) => break,
+            Err(e) => {
+                pb.abandon();
+                return Err(e);
+            }
+        }
+    }
+    pb.finish_and_clear();
+
+    Ok(body)
+}
+
+/// Build `ga_cards.json`, keeping editions created on or after `min_date` and at
+/// most `editions_per_card` editions of each card
+pub async fn fetch_ga_all_cards(
+    client: &reqwest::Client,
+    directory: &str,
+    editions_per_card: Option<usize>,
+    min_date: Option<NaiveDate>,
+    max_age: Option<Duration>,
+    pretty_json: bool,
+) -> io::Result<Vec<String>> {
     let tcg_type = TcgType::Ga;
-    let existing_files = check_json_files(directory, &tcg_type);
+    let existing_files = check_json_files(directory, &tcg_type, max_age);
 
     if !existing_files.is_empty() {
         println!("Using existing JSON files");
+        if editions_per_card.is_some() || min_date.is_some() {
+            println!("Note: the editions-per-card cap and min-date filter apply when ga_cards.json is built; pass --refresh-json to rebuild it");
+        }
         return Ok(existing_files);
     }
 
     println!("Fetching GA card data from API...");
-    let client = reqwest::Client::new();
 
     // First, get all card names and slugs
-    let response = client
-        .get(GaApi::get_api_url())
-        .header("User-Agent", get_user_agent())
-        .header("Accept", "application/json")
-        .send()
-        .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Request error: {}", e)))?;
-
-    let cards: Vec<GaCard> = response.json().await.map_err(|e| {
-        io::Error::new(io::ErrorKind::Other, format!("Failed to parse JSON: {}", e))
-    })?;
+    let cards = fetch_ga_card_list(client).await?;
 
     println!(
         "Found {} cards, fetching detailed information...",
         cards.len()
     );
 
-    // Create a temporary file to store all the card data
+    // Editions are appended to a partial file as card details resolve so an
+    // interrupted run can resume without refetching completed cards
     let temp_file = Path::new(directory).join("ga_cards.json");
-    let mut all_cards_data = Vec::new();
+    let partial_file = Path::new(directory).join(GA_PARTIAL_FILE);
+    let mut progress = load_partial_progress(&partial_file)?;
+    let processed: HashSet<String> = progress.iter().map(|card| card.card.clone()).collect();
+    if !processed.is_empty() {
+        println!(
+            "Resuming from {}: {} cards already processed",
+            partial_file.display(),
+            processed.len()
+        );
+    }
+
+    let remaining: Vec<GaCard> = cards
+        .into_iter()
+        .filter(|card| !processed.contains(&card.slug))
+        .collect();
+
+    let mut partial = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&partial_file)?;
+
+    let pb = ProgressBar::new(remaining.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.set_message("card details");
 
     // Use parallel processing to fetch card details
-    let card_details = futures::stream::iter(cards.into_iter().map(|card| {
-        let client = &client;
-        async move {
-            match fetch_ga_card_detail(client, &card.slug).await {
-                Ok(detail) => Some(detail),
-                Err(e) => {
-                    eprintln!("Failed to fetch details for {}: {}", card.slug, e);
-                    None
-                }
+    let mut card_details = futures::stream::iter(remaining.into_iter().map(|card| async move {
+        let detail = fetch_ga_card_detail(client, &card.slug).await;
+        (card.slug, detail)
+    }))
+    .buffer_unordered(10); // Process 10 cards concurrently
+
+    // Failed cards stay out of the partial file, so a rerun fetches just those
+    let mut failed = 0;
+    while let Some((slug, detail)) = card_details.next().await {
+        pb.inc(1);
+        let detail = match detail {
+            Ok(detail) => detail,
+            Err(e) => {
+                pb.println(format!("Failed to fetch details for {}: {}", slug, e));
+                failed += 1;
+                continue;
             }
+        };
+
+        let card_progress = GaCardProgress::from_detail(slug, detail);
+        if card_progress.no_editions {
+            pb.println(format!(
+                "Warning: card '{}' has no editions, no image to download",
+                card_progress.name
+            ));
         }
-    }))
-    .buffer_unordered(10) // Process 10 cards concurrently
-    .collect::<Vec<_>>()
-    .await;
+        for edition in &card_progress.invalid_images {
+            pb.println(format!(
+                "Warning: edition '{}' has an invalid image path, skipping",
+                edition
+            ));
+        }
+
+        serde_json::to_writer(&mut partial, &card_progress)?;
+        partial.write_all(b"\n")?;
+        progress.push(card_progress);
+    }
+
+    pb.finish_with_message("card details fetched");
+    drop(partial);
+
+    // Writing ga_cards.json now would leave the failed cards out of every later run
+    if failed > 0 {
+        return Err(io::Error::other(format!(
+            "Failed to fetch details for {} of {} cards; {} keeps the others, rerun to retry only the failed ones",
+            failed,
+            failed + progress.len(),
+            partial_file.display()
+        )));
+    }
 
     // Collect all edition data - one entry per edition
-    for card_detail in card_details.into_iter().flatten() {
-        for edition in card_detail.editions {
-            all_cards_data.push(serde_json::json!({
-                "slug": edition.slug,
-                "image": format!("https://api.gatcg.com{}", edition.image)
-            }));
+    let mut all_cards_data = Vec::new();
+    let mut cards_without_editions = Vec::new();
+    let mut invalid_images = Vec::new();
+    let mut dropped_editions = 0;
+    let mut dated_out_editions = 0;
+    for mut card_progress in progress {
+        if card_progress.no_editions {
+            cards_without_editions.push(card_progress.name);
+        }
+        invalid_images.extend(card_progress.invalid_images);
+        if let Some(min_date) = min_date {
+            let before = card_progress.entries.len();
+            card_progress
+                .entries
+                .retain(|entry| created_on_or_after(entry, min_date));
+            dated_out_editions += before - card_progress.entries.len();
+        }
+        if let Some(limit) = editions_per_card {
+            if card_progress.entries.len() > limit {
+                dropped_editions += card_progress.entries.len() - limit;
+                card_progress.entries.truncate(limit);
+            }
         }
+        all_cards_data.extend(card_progress.entries);
     }
 
-    // Write the collected data to a JSON file
-    let json_data = serde_json::to_string_pretty(&all_cards_data).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!("Failed to serialize JSON: {}", e),
-        )
-    })?;
+    // Write the collected data to a JSON file; compact by default, since indentation
+    // adds a lot of size across tens of thousands of editions
+    let json_data = if pretty_json {
+        serde_json::to_string_pretty(&all_cards_data)
+    } else {
+        serde_json::to_string(&all_cards_data)
+    }
+    .map_err(|e| io::Error::other(format!("Failed to serialize JSON: {}", e)))?;
 
     std::fs::write(&temp_file, json_data)?;
+    std::fs::remove_file(&partial_file)?;
     println!("Successfully downloaded: {}", temp_file.display());
 
+    println!("Collected {} editions", all_cards_data.len());
+    if !cards_without_editions.is_empty() {
+        println!(
+            "Skipped {} cards with no editions: {}",
+            cards_without_editions.len(),
+            cards_without_editions.join(", ")
+        );
+    }
+    if dated_out_editions > 0 {
+        println!(
+            "Filtered out {} editions created before {}",
+            dated_out_editions,
+            min_date.unwrap_or_default()
+        );
+    }
+    if dropped_editions > 0 {
+        println!(
+            "Dropped {} editions beyond the per-card limit of {}",
+            dropped_editions,
+            editions_per_card.unwrap_or_default()
+        );
+    }
+    if !invalid_images.is_empty() {
+        println!(
+            "Skipped {} editions with invalid image paths: {}",
+            invalid_images.len(),
+            invalid_images.join(", ")
+        );
+    }
+
     Ok(vec![temp_file.to_string_lossy().into_owned()])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_with_empty_editions_is_flagged() {
+        let detail: GaCardDetail =
+            serde_json::from_str(r#"{"name": "Lorraine", "editions": []}"#).unwrap();
+        let progress = GaCardProgress::from_detail("lorraine".to_string(), detail);
+
+        assert!(progress.no_editions);
+        assert!(progress.entries.is_empty());
+        assert!(progress.invalid_images.is_empty());
+    }
+
+    #[test]
+    fn card_with_missing_editions_is_flagged() {
+        let detail: GaCardDetail = serde_json::from_str(r#"{"name": "Lorraine"}"#).unwrap();
+        let progress = GaCardProgress::from_detail("lorraine".to_string(), detail);
+
+        assert!(progress.no_editions);
+        assert!(progress.entries.is_empty());
+    }
+
+    #[test]
+    fn card_with_editions_is_not_flagged() {
+        let detail: GaCardDetail = serde_json::from_str(
+            r#"{"name": "Lorraine", "editions": [{"slug": "lorraine-doap", "image": "/cards/images/lorraine-doap.jpg"}]}"#,
+        )
+        .unwrap();
+        let progress = GaCardProgress::from_detail("lorraine".to_string(), detail);
+
+        assert!(!progress.no_editions);
+        assert_eq!(progress.entries.len(), 1);
+    }
+}
@@ -1,20 +1,125 @@
+use crate::utils::images::{CardFilter, LoadedCards};
+use chrono::NaiveDate;
 use clap::ValueEnum;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
 pub enum TcgType {
     /// Magic: The Gathering
     Mtg,
     /// Grand Archive
     Ga,
+    /// Any JSON card API, described by --source-url, --id-path and --image-path
+    Custom,
 }
 
 // Unified card structure for both MTG and GA
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnifiedCard {
     pub id: String,
     pub image_url: String,
+    pub name: Option<String>,
+    pub set: Option<String>,
+    pub rarity: Option<String>,
+    pub collector_number: Option<String>,
+    pub lang: Option<String>,
+    /// Directory name used instead of the id, see `apply_dir_naming`
+    pub dir_name: Option<String>,
+}
+
+impl UnifiedCard {
+    /// Directory for this card relative to `data/train`
+    pub fn relative_dir(&self, group_by_set: bool) -> PathBuf {
+        let name = self.dir_name.as_deref().unwrap_or(&self.id);
+        match (&self.set, group_by_set) {
+            (Some(set), true) => PathBuf::from(set).join(name),
+            _ => PathBuf::from(name),
+        }
+    }
+}
+
+impl TcgType {
+    /// The implementation behind this game
+    pub fn source(&self) -> &'static dyn TcgSource {
+        match self {
+            TcgType::Mtg => &mtg::MtgApi,
+            TcgType::Ga => &ga::GaApi,
+            TcgType::Custom => &custom::CustomApi,
+        }
+    }
+}
+
+/// Settings for building the card JSON; each game reads the ones that apply to it
+#[derive(Debug, Clone)]
+pub struct MetadataOptions {
+    /// Data source for MTG cards
+    pub source: mtg::MtgSource,
+    /// Scryfall bulk data file to download
+    pub scryfall_dataset: mtg::ScryfallDataset,
+    /// Keep at most this many editions of each GA card
+    pub editions_per_card: Option<usize>,
+    /// Keep only GA editions created on or after this date
+    pub min_date: Option<NaiveDate>,
+    /// Fetch the card JSON again when the cached file is older than this
+    pub max_age: Option<Duration>,
+    /// Write ga_cards.json indented instead of compact
+    pub pretty_json: bool,
+    /// Where the custom source's cards come from, when one was given
+    pub custom: Option<custom::CustomSource>,
+}
+
+/// Everything that differs between supported games
+///
+/// Adding a game means one implementation of this trait and a `TcgType` variant
+/// mapped to it in [`TcgType::source`].
+pub trait TcgSource: Send + Sync {
+    /// Name of the game, for messages
+    fn name(&self) -> &'static str;
+
+    /// API endpoint the card data comes from, checked by `doctor`
+    fn api_url(&self) -> &'static str;
+
+    /// Name of the card JSON file without its extension, e.g. `mtg_cards`
+    fn json_file_stem(&self) -> &'static str;
+
+    /// Stems of every card JSON file the source may write, `json_file_stem` first
+    fn json_file_stems(&self) -> Vec<&'static str> {
+        vec![self.json_file_stem()]
+    }
+
+    /// Extension of downloaded images before they are processed
+    fn temp_extension(&self) -> &'static str;
+
+    /// Parts of image URLs that mark a placeholder rather than a card image
+    fn placeholder_urls(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether cards carry a set code, needed by set-based options
+    fn has_sets(&self) -> bool;
+
+    /// Card back shared by the whole game, downloaded with `--include-backs`
+    fn card_back(&self) -> Option<UnifiedCard> {
+        None
+    }
+
+    /// Build (or reuse) the card JSON in `directory`, returning the files to download from
+    fn fetch_metadata<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        directory: &'a str,
+        options: &'a MetadataOptions,
+    ) -> BoxFuture<'a, io::Result<Vec<String>>>;
+
+    /// Normalize the contents of a card JSON file into unified cards with images
+    fn parse_cards(&self, json: &str, filter: &CardFilter) -> io::Result<LoadedCards>;
 }
 
 // Re-export TCG-specific modules
+pub mod custom;
 pub mod ga;
 pub mod mtg;
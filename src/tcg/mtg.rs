@@ -1,9 +1,224 @@
-use crate::tcg::TcgType;
-use crate::utils::files::check_json_files;
-use crate::utils::http::{download_json_data, get_user_agent};
+use crate::tcg::{MetadataOptions, TcgSource, TcgType, UnifiedCard};
+use crate::utils::files::{check_json_files, is_fresh};
+use crate::utils::http::download_json_data;
+use crate::utils::images::{CardFilter, LoadedCards};
+use crate::utils::json_shape::JsonShape;
+use clap::ValueEnum;
+use futures::future::BoxFuture;
 use reqwest;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Upstream source for MTG card data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MtgSource {
+    /// Scryfall bulk data
+    Scryfall,
+    /// MTGJSON AllPrintings, with images served from Scryfall
+    Mtgjson,
+}
+
+/// Which Scryfall bulk data file to download
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScryfallDataset {
+    /// One card per Oracle id, using the most recent printing
+    #[value(name = "oracle_cards")]
+    OracleCards,
+    /// One card per unique artwork, ideal for art datasets
+    #[value(name = "unique_artwork")]
+    UniqueArtwork,
+    /// Every card in English or its printed language
+    #[value(name = "default_cards")]
+    DefaultCards,
+    /// Every printing in every language
+    #[value(name = "all_cards")]
+    AllCards,
+}
+
+impl ScryfallDataset {
+    /// The `type` of this dataset in Scryfall's bulk data listing
+    pub fn bulk_type(self) -> &'static str {
+        match self {
+            Self::OracleCards => "oracle_cards",
+            Self::UniqueArtwork => "unique_artwork",
+            Self::DefaultCards => "default_cards",
+            Self::AllCards => "all_cards",
+        }
+    }
+}
+
+/// Printed finish of an MTG card, as listed in Scryfall's `finishes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum Finish {
+    Nonfoil,
+    Foil,
+    Etched,
+}
+
+impl Finish {
+    /// Name of this finish in Scryfall and MTGJSON data
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Nonfoil => "nonfoil",
+            Self::Foil => "foil",
+            Self::Etched => "etched",
+        }
+    }
+}
+
+/// Quality of a card's image as reported in Scryfall's `image_status`, lowest first
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize,
+)]
+pub enum ImageStatus {
+    /// No image at all
+    Missing,
+    /// A "preview coming soon" stand-in
+    Placeholder,
+    /// A scan below full resolution, e.g. of a card not yet released
+    #[default]
+    Lowres,
+    /// A full-resolution scan
+    HighresScan,
+}
+
+impl ImageStatus {
+    /// Name of this status in Scryfall data
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Placeholder => "placeholder",
+            Self::Lowres => "lowres",
+            Self::HighresScan => "highres_scan",
+        }
+    }
+
+    fn from_scryfall(status: &str) -> Option<Self> {
+        [
+            Self::Missing,
+            Self::Placeholder,
+            Self::Lowres,
+            Self::HighresScan,
+        ]
+        .into_iter()
+        .find(|known| known.as_str() == status)
+    }
+}
+
+/// A single card object from the Scryfall bulk data
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Card {
+    pub id: String,
+    pub name: String,
+    pub set: String,
+    pub rarity: String,
+    pub image_uris: Option<ImageUris>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_faces: Option<Vec<CardFace>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set_type: Option<String>,
+    #[serde(default)]
+    pub digital: bool,
+    #[serde(default)]
+    pub promo: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub promo_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub finishes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collector_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_status: Option<String>,
+    /// Shared by every printing of the same card; missing on reversible cards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oracle_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub released_at: Option<String>,
+}
+
+/// Layouts used by Scryfall for tokens, emblems and other non-card objects
+const NON_CARD_LAYOUTS: &[&str] = &["token", "double_faced_token", "emblem", "art_series"];
+
+impl Card {
+    /// Whether this object is a token, emblem or art card rather than a real card
+    pub fn is_token(&self) -> bool {
+        self.layout
+            .as_deref()
+            .is_some_and(|layout| NON_CARD_LAYOUTS.contains(&layout))
+            || self.set_type.as_deref() == Some("token")
+    }
+
+    /// Whether this card is printed in the given finish
+    ///
+    /// Cards without finish data (e.g. from older cached JSON) always match.
+    pub fn has_finish(&self, finish: Finish) -> bool {
+        self.finishes.is_empty() || self.finishes.iter().any(|f| f == finish.as_str())
+    }
+
+    /// Whether this card's image is at least `min` quality
+    ///
+    /// Cards without a status Scryfall documents (e.g. from MTGJSON) always match.
+    pub fn has_image_status(&self, min: ImageStatus) -> bool {
+        self.image_status
+            .as_deref()
+            .and_then(ImageStatus::from_scryfall)
+            .is_none_or(|status| status >= min)
+    }
+}
+
+/// Keep one printing per oracle id, returning how many printings were dropped
+///
+/// The printing with the best `image_status` wins, then the most recently
+/// released. Cards without an oracle id are all kept.
+fn dedupe_by_oracle_id(cards: &mut Vec<Card>) -> usize {
+    let preference = |card: &Card| {
+        (
+            card.image_status
+                .as_deref()
+                .and_then(ImageStatus::from_scryfall),
+            card.released_at.clone(),
+        )
+    };
+
+    let mut best: HashMap<&str, usize> = HashMap::new();
+    for (index, card) in cards.iter().enumerate() {
+        let Some(oracle_id) = card.oracle_id.as_deref() else {
+            continue;
+        };
+        let chosen = best.entry(oracle_id).or_insert(index);
+        if preference(card) > preference(&cards[*chosen]) {
+            *chosen = index;
+        }
+    }
+    let keep: HashSet<usize> = best.into_values().collect();
+
+    let before = cards.len();
+    let mut index = 0;
+    cards.retain(|card| {
+        let kept = card.oracle_id.is_none() || keep.contains(&index);
+        index += 1;
+        kept
+    });
+    before - cards.len()
+}
+
+/// One face of a multi-faced card; double-faced cards carry images per face
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CardFace {
+    pub image_uris: Option<ImageUris>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImageUris {
+    pub png: String,
+}
 
 #[derive(Debug, Deserialize)]
 struct BulkDataItem {
@@ -18,23 +233,419 @@ struct BulkDataResponse {
     data: Vec<BulkDataItem>,
 }
 
+/// Scryfall's list of bulk data files
+const BULK_DATA_SHAPE: JsonShape = JsonShape {
+    source: "Scryfall bulk data index",
+    records_at: &["data"],
+    keyed_records: false,
+    record_fields: &["type", "download_uri"],
+};
+
+/// A Scryfall bulk data file, or the cards normalized from MTGJSON
+const CARDS_SHAPE: JsonShape = JsonShape {
+    source: "MTG card data",
+    records_at: &[],
+    keyed_records: false,
+    record_fields: &["id", "name", "set"],
+};
+
+/// MTGJSON's AllPrintings, sets keyed by code
+const MTGJSON_SHAPE: JsonShape = JsonShape {
+    source: "MTGJSON AllPrintings",
+    records_at: &["data"],
+    keyed_records: true,
+    record_fields: &["cards"],
+};
+
+#[derive(Debug, Deserialize)]
+struct MtgJsonFile {
+    data: HashMap<String, MtgJsonSet>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MtgJsonSet {
+    #[serde(rename = "releaseDate", default)]
+    release_date: Option<String>,
+    #[serde(default)]
+    cards: Vec<MtgJsonCard>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MtgJsonCard {
+    name: String,
+    #[serde(rename = "setCode")]
+    set_code: String,
+    rarity: String,
+    #[serde(rename = "isOnlineOnly", default)]
+    is_online_only: bool,
+    #[serde(rename = "isPromo", default)]
+    is_promo: bool,
+    #[serde(rename = "promoTypes", default)]
+    promo_types: Vec<String>,
+    #[serde(default)]
+    finishes: Vec<String>,
+    number: Option<String>,
+    identifiers: MtgJsonIdentifiers,
+}
+
+#[derive(Debug, Deserialize)]
+struct MtgJsonIdentifiers {
+    #[serde(rename = "scryfallId")]
+    scryfall_id: Option<String>,
+    #[serde(rename = "scryfallOracleId")]
+    scryfall_oracle_id: Option<String>,
+}
+
 pub struct MtgApi;
 
 impl MtgApi {
-    fn get_api_url() -> &'static str {
+    pub(crate) fn get_api_url() -> &'static str {
         "https://api.scryfall.com/bulk-data"
     }
 
     fn get_api_type() -> &'static str {
         "mtg_cards"
     }
+
+    /// Image of the generic MTG card back shared by all single-faced cards
+    pub fn get_card_back_url() -> &'static str {
+        "https://backs.scryfall.io/large/0/a/0aeebaf5-8c7d-4636-9e82-8c27447861f7.jpg"
+    }
+
+    fn get_mtgjson_url() -> &'static str {
+        "https://mtgjson.com/api/v5/AllPrintings.json"
+    }
+
+    fn get_mtgjson_type() -> &'static str {
+        "mtgjson_cards"
+    }
 }
 
-pub async fn fetch_mtg_bulk_data(directory: &str) -> io::Result<Vec<String>> {
+impl TcgSource for MtgApi {
+    fn name(&self) -> &'static str {
+        "Magic: The Gathering"
+    }
+
+    fn api_url(&self) -> &'static str {
+        Self::get_api_url()
+    }
+
+    fn json_file_stem(&self) -> &'static str {
+        Self::get_api_type()
+    }
+
+    fn json_file_stems(&self) -> Vec<&'static str> {
+        vec![Self::get_api_type(), Self::get_mtgjson_type()]
+    }
+
+    fn temp_extension(&self) -> &'static str {
+        "png"
+    }
+
+    fn placeholder_urls(&self) -> &'static [&'static str] {
+        // Scryfall links this image for cards whose scan is not available yet
+        &["errors.scryfall.com/soon.jpg"]
+    }
+
+    fn has_sets(&self) -> bool {
+        true
+    }
+
+    /// The generic MTG card back, stored once in its own directory
+    fn card_back(&self) -> Option<UnifiedCard> {
+        Some(UnifiedCard {
+            id: "card-back".to_string(),
+            image_url: Self::get_card_back_url().to_string(),
+            name: None,
+            set: None,
+            rarity: None,
+            collector_number: None,
+            lang: None,
+            dir_name: None,
+        })
+    }
+
+    fn fetch_metadata<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+        directory: &'a str,
+        options: &'a MetadataOptions,
+    ) -> BoxFuture<'a, io::Result<Vec<String>>> {
+        Box::pin(fetch_mtg_data(
+            client,
+            directory,
+            options.source,
+            options.scryfall_dataset,
+            options.max_age,
+        ))
+    }
+
+    fn parse_cards(&self, json: &str, filter: &CardFilter) -> io::Result<LoadedCards> {
+        let mut loaded = LoadedCards::default();
+        // Parse each entry separately so one malformed card doesn't fail the whole file
+        let document: serde_json::Value = serde_json::from_str(json)?;
+        CARDS_SHAPE.check(&document)?;
+        let entries: Vec<serde_json::Value> = serde_json::from_value(document)?;
+        let mut mtg_cards: Vec<Card> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match serde_json::from_value::<Card>(entry) {
+                Ok(card) => mtg_cards.push(card),
+                Err(e) => {
+                    let (count, _) = loaded.unparseable.get_or_insert_with(|| (0, e.to_string()));
+                    *count += 1;
+                }
+            }
+        }
+        if !filter.include_tokens {
+            let before = mtg_cards.len();
+            mtg_cards.retain(|card| !card.is_token());
+            loaded.excluded_tokens = before - mtg_cards.len();
+        }
+        if filter.exclude_digital {
+            let before = mtg_cards.len();
+            mtg_cards.retain(|card| !card.digital);
+            loaded.excluded_digital = before - mtg_cards.len();
+        }
+        let before = mtg_cards.len();
+        mtg_cards.retain(|card| card.has_image_status(filter.min_image_status));
+        loaded.excluded_image_status = before - mtg_cards.len();
+        if filter.exclude_promos {
+            let before = mtg_cards.len();
+            mtg_cards.retain(|card| !card.promo);
+            loaded.excluded_promos = before - mtg_cards.len();
+        }
+        if let Some(finish) = filter.finish {
+            let before = mtg_cards.len();
+            mtg_cards.retain(|card| card.has_finish(finish));
+            loaded.excluded_finish = Some((finish, before - mtg_cards.len()));
+        }
+        // After the other filters, so the printing kept is one they allow
+        if filter.dedupe_by_oracle_id {
+            loaded.collapsed_printings = dedupe_by_oracle_id(&mut mtg_cards);
+        }
+        let mut cards = Vec::with_capacity(mtg_cards.len());
+        for card in mtg_cards {
+            let id = card.id.clone();
+            let has_face_images = card.image_uris.is_none()
+                && card
+                    .card_faces
+                    .iter()
+                    .flatten()
+                    .any(|face| face.image_uris.is_some());
+            let unified = mtg_unified_cards(card, filter.include_backs);
+            if unified.is_empty() {
+                if has_face_images && !filter.include_backs {
+                    loaded.faces_only += 1;
+                } else {
+                    loaded.imageless.push(id);
+                }
+            }
+            cards.extend(unified);
+        }
+        loaded.cards = cards;
+        Ok(loaded)
+    }
+}
+
+/// Convert an MTG card into unified cards, optionally including double-faced backs
+fn mtg_unified_cards(card: Card, include_backs: bool) -> Vec<UnifiedCard> {
+    let make_card = |id: String, image_url: String| UnifiedCard {
+        id,
+        image_url,
+        name: Some(card.name.clone()),
+        set: Some(card.set.clone()),
+        rarity: Some(card.rarity.clone()),
+        collector_number: card.collector_number.clone(),
+        lang: card.lang.clone(),
+        dir_name: None,
+    };
+
+    if let Some(image_uris) = &card.image_uris {
+        return vec![make_card(card.id.clone(), image_uris.png.clone())];
+    }
+
+    if !include_backs {
+        return Vec::new();
+    }
+
+    let face_urls: Vec<String> = card
+        .card_faces
+        .iter()
+        .flatten()
+        .filter_map(|face| face.image_uris.as_ref().map(|uris| uris.png.clone()))
+        .collect();
+
+    match face_urls.as_slice() {
+        [front, back, ..] => vec![
+            make_card(card.id.clone(), front.clone()),
+            make_card(format!("{}-back", card.id), back.clone()),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Build the Scryfall PNG image URL for a card from its Scryfall id
+fn scryfall_png_url(scryfall_id: &str) -> String {
+    let mut chars = scryfall_id.chars();
+    let first = chars.next().unwrap_or('0');
+    let second = chars.next().unwrap_or('0');
+    format!(
+        "https://cards.scryfall.io/png/front/{}/{}/{}.png",
+        first, second, scryfall_id
+    )
+}
+
+/// Fetch MTG card data from the selected source
+pub async fn fetch_mtg_data(
+    client: &reqwest::Client,
+    directory: &str,
+    source: MtgSource,
+    dataset: ScryfallDataset,
+    max_age: Option<Duration>,
+) -> io::Result<Vec<String>> {
+    match source {
+        MtgSource::Scryfall => fetch_mtg_bulk_data(client, directory, dataset, max_age).await,
+        MtgSource::Mtgjson => fetch_mtgjson_data(client, directory, max_age).await,
+    }
+}
+
+/// Download MTGJSON's AllPrintings file and normalize it into Scryfall-shaped cards
+pub async fn fetch_mtgjson_data(
+    client: &reqwest::Client,
+    directory: &str,
+    max_age: Option<Duration>,
+) -> io::Result<Vec<String>> {
+    let file_type = MtgApi::get_mtgjson_type();
+    let file_path = Path::new(directory).join(format!("{}.json", file_type));
+
+    if is_fresh(&file_path, max_age) {
+        println!("Using existing JSON files");
+        return Ok(vec![file_path.to_string_lossy().into_owned()]);
+    }
+
+    println!("Fetching AllPrintings from MTGJSON...");
+    let raw_path = download_json_data(
+        client,
+        "mtgjson_allprintings",
+        MtgApi::get_mtgjson_url(),
+        directory,
+    )
+    .await?;
+
+    let raw_content = std::fs::read_to_string(&raw_path)?;
+    // AllPrintings is too large to parse twice, so its shape is only checked
+    // to explain a failure
+    let all_printings: MtgJsonFile = serde_json::from_str(&raw_content).map_err(|e| {
+        serde_json::from_str(&raw_content)
+            .ok()
+            .and_then(|document| MTGJSON_SHAPE.check(&document).err())
+            .unwrap_or_else(|| io::Error::other(format!("Failed to parse MTGJSON data: {}", e)))
+    })?;
+    drop(raw_content);
+
+    // Double-faced cards appear once per face with the same Scryfall id
+    let mut seen = HashSet::new();
+    let cards: Vec<Card> = all_printings
+        .data
+        .into_values()
+        .flat_map(|set| {
+            let release_date = set.release_date;
+            set.cards
+                .into_iter()
+                .map(move |card| (card, release_date.clone()))
+        })
+        .filter_map(|(card, released_at)| {
+            let scryfall_id = card.identifiers.scryfall_id?;
+            if !seen.insert(scryfall_id.clone()) {
+                return None;
+            }
+            Some(Card {
+                image_uris: Some(ImageUris {
+                    png: scryfall_png_url(&scryfall_id),
+                }),
+                id: scryfall_id,
+                name: card.name,
+                set: card.set_code.to_lowercase(),
+                rarity: card.rarity,
+                card_faces: None,
+                layout: None,
+                set_type: None,
+                digital: card.is_online_only,
+                promo: card.is_promo,
+                promo_types: card.promo_types,
+                finishes: card.finishes,
+                collector_number: card.number,
+                // AllPrintings only lists the English printing of each card
+                lang: Some("en".to_string()),
+                // MTGJSON does not track image quality
+                image_status: None,
+                oracle_id: card.identifiers.scryfall_oracle_id,
+                released_at,
+            })
+        })
+        .collect();
+
+    println!("Normalized {} MTGJSON printings", cards.len());
+
+    let json_data = serde_json::to_string(&cards)
+        .map_err(|e| io::Error::other(format!("Failed to serialize JSON: {}", e)))?;
+    std::fs::write(&file_path, json_data)?;
+    std::fs::remove_file(&raw_path)?;
+
+    println!("Successfully downloaded: {}", file_path.display());
+    Ok(vec![file_path.to_string_lossy().into_owned()])
+}
+
+/// File next to `mtg_cards.json` naming the Scryfall dataset it was downloaded from
+fn dataset_path(directory: &str) -> PathBuf {
+    Path::new(directory).join(format!("{}.dataset", MtgApi::get_api_type()))
+}
+
+/// The cached `mtg_cards.json`, if it is fresh and holds `dataset`
+///
+/// Every dataset is saved under the same name, so a file from another dataset,
+/// or one whose dataset was never recorded, is treated as stale.
+fn cached_bulk_files(
+    directory: &str,
+    dataset: ScryfallDataset,
+    max_age: Option<Duration>,
+) -> Vec<String> {
+    let existing_files = check_json_files(directory, &TcgType::Mtg, max_age);
+    if existing_files.is_empty() {
+        return existing_files;
+    }
+
+    match std::fs::read_to_string(dataset_path(directory)) {
+        Ok(cached) if cached.trim() == dataset.bulk_type() => existing_files,
+        Ok(cached) => {
+            println!(
+                "{}.json holds the {} dataset, fetching {}",
+                MtgApi::get_api_type(),
+                cached.trim(),
+                dataset.bulk_type()
+            );
+            Vec::new()
+        }
+        Err(_) => {
+            println!(
+                "{}.json does not record its Scryfall dataset, fetching {}",
+                MtgApi::get_api_type(),
+                dataset.bulk_type()
+            );
+            Vec::new()
+        }
+    }
+}
+
+pub async fn fetch_mtg_bulk_data(
+    client: &reqwest::Client,
+    directory: &str,
+    dataset: ScryfallDataset,
+    max_age: Option<Duration>,
+) -> io::Result<Vec<String>> {
     let file_type = MtgApi::get_api_type(); // For file naming
-    let scryfall_type = "all_cards"; // For Scryfall API
-    let tcg_type = TcgType::Mtg;
-    let existing_files = check_json_files(directory, &tcg_type);
+    let scryfall_type = dataset.bulk_type(); // For Scryfall API
+    let existing_files = cached_bulk_files(directory, dataset, max_age);
 
     if !existing_files.is_empty() {
         println!("Using existing JSON files");
@@ -42,38 +653,38 @@ pub async fn fetch_mtg_bulk_data(directory: &str) -> io::Result<Vec<String>> {
     }
 
     println!("Fetching bulk data from Scryfall API...");
-    let client = reqwest::Client::new();
 
     let response = client
         .get(MtgApi::get_api_url())
-        .header("User-Agent", get_user_agent())
         .send()
         .await
-        .map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to send request: {}", e),
-            )
-        })?;
+        .map_err(|e| io::Error::other(format!("Failed to send request: {}", e)))?;
 
     println!("Response status: {}", response.status());
 
-    let response_text = response.text().await.map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!("Failed to get response text: {}", e),
-        )
-    })?;
+    let response_text = response
+        .text()
+        .await
+        .map_err(|e| io::Error::other(format!("Failed to get response text: {}", e)))?;
 
-    let bulk_data: BulkDataResponse = serde_json::from_str(&response_text).map_err(|e| {
-        io::Error::new(io::ErrorKind::Other, format!("Failed to parse JSON: {}", e))
-    })?;
+    let document: serde_json::Value = serde_json::from_str(&response_text)
+        .map_err(|e| io::Error::other(format!("Failed to parse JSON: {}", e)))?;
+    BULK_DATA_SHAPE.check(&document)?;
+    let bulk_data: BulkDataResponse = serde_json::from_value(document)
+        .map_err(|e| io::Error::other(format!("Failed to parse JSON: {}", e)))?;
 
     let mut downloaded_files = Vec::new();
+    let available: Vec<String> = bulk_data
+        .data
+        .iter()
+        .map(|item| item.data_type.clone())
+        .collect();
 
     for item in bulk_data.data {
         if item.data_type == scryfall_type {
-            let file_path = download_json_data(&file_type, &item.download_uri, directory).await?;
+            let file_path =
+                download_json_data(client, file_type, &item.download_uri, directory).await?;
+            std::fs::write(dataset_path(directory), scryfall_type)?;
             downloaded_files.push(file_path);
             break;
         }
@@ -83,11 +694,52 @@ pub async fn fetch_mtg_bulk_data(directory: &str) -> io::Result<Vec<String>> {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
             format!(
-                "Data type '{}' not found in Scryfall bulk data",
-                scryfall_type
+                "Data type '{}' not found in Scryfall bulk data (available: {})",
+                scryfall_type,
+                available.join(", ")
             ),
         ));
     }
 
     Ok(downloaded_files)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tcg-fetch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn bulk_file_is_reused_only_for_its_dataset() {
+        let dir = test_dir("mtg-dataset");
+        let directory = dir.to_str().unwrap();
+        fs::write(dir.join("mtg_cards.json"), "[]").unwrap();
+        fs::write(dataset_path(directory), "all_cards").unwrap();
+
+        assert_eq!(
+            cached_bulk_files(directory, ScryfallDataset::AllCards, None).len(),
+            1
+        );
+        assert!(cached_bulk_files(directory, ScryfallDataset::UniqueArtwork, None).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bulk_file_without_a_recorded_dataset_is_stale() {
+        let dir = test_dir("mtg-no-dataset");
+        let directory = dir.to_str().unwrap();
+        fs::write(dir.join("mtg_cards.json"), "[]").unwrap();
+
+        assert!(cached_bulk_files(directory, ScryfallDataset::AllCards, None).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
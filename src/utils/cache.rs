@@ -0,0 +1,68 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Content-addressable store of downloaded source images keyed by their URL
+///
+/// Entries hold the raw bytes as served, before resizing or re-encoding, so one
+/// cache can be shared by datasets with different processing settings.
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+    dir: PathBuf,
+}
+
+impl ImageCache {
+    /// Open a cache directory, creating it if needed
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Location of the cache entry for a source URL
+    fn entry_path(&self, url: &str) -> PathBuf {
+        let key = Sha256::digest(url.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        // Fan out over subdirectories to keep directory listings small
+        self.dir.join(&key[..2]).join(key)
+    }
+
+    /// Place the cached image for `url` at `target`, returning false on a cache miss
+    ///
+    /// Hard links are tried first so cached images take no extra space; a copy is
+    /// made when the cache lives on a different filesystem.
+    pub fn restore(&self, url: &str, target: &Path) -> io::Result<bool> {
+        let entry = self.entry_path(url);
+        if !entry.exists() {
+            return Ok(false);
+        }
+
+        if target.exists() {
+            fs::remove_file(target)?;
+        }
+        if fs::hard_link(&entry, target).is_err() {
+            fs::copy(&entry, target)?;
+        }
+        Ok(true)
+    }
+
+    /// Store the downloaded bytes for `url`
+    pub fn store(&self, url: &str, bytes: &[u8]) -> io::Result<()> {
+        let entry = self.entry_path(url);
+        if entry.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = entry.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary name first so concurrent readers never see a partial entry
+        let partial = entry.with_extension("partial");
+        fs::write(&partial, bytes)?;
+        fs::rename(&partial, &entry)
+    }
+}
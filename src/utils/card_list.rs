@@ -0,0 +1,92 @@
+use crate::tcg::UnifiedCard;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Cards requested by a `--card-list` file, with which of them have been found
+///
+/// Each non-empty line is a card id, GA slug or card name; lines starting with
+/// `#` are comments. Matching ignores case, and an id also selects that card's
+/// `-back` face.
+#[derive(Debug)]
+pub struct CardList {
+    entries: Vec<String>,
+    /// Lowercased entry -> index into `entries`
+    lookup: HashMap<String, usize>,
+    found: Mutex<Vec<bool>>,
+}
+
+impl CardList {
+    /// Read a card list file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Failed to read card list {}: {}", path.display(), e),
+            )
+        })?;
+
+        let mut entries = Vec::new();
+        let mut lookup = HashMap::new();
+        for line in content.lines() {
+            let entry = line.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            if let Entry::Vacant(slot) = lookup.entry(entry.to_lowercase()) {
+                slot.insert(entries.len());
+                entries.push(entry.to_string());
+            }
+        }
+
+        if entries.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Card list {} has no entries", path.display()),
+            ));
+        }
+
+        let found = Mutex::new(vec![false; entries.len()]);
+        Ok(Self {
+            entries,
+            lookup,
+            found,
+        })
+    }
+
+    /// Number of distinct cards requested
+    pub fn requested(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Keep only the cards the list asks for, remembering which entries matched
+    pub fn retain(&self, cards: &mut Vec<UnifiedCard>) {
+        let mut found = self.found.lock().unwrap_or_else(|e| e.into_inner());
+        cards.retain(|card| {
+            let id = card.id.strip_suffix("-back").unwrap_or(&card.id);
+            let matches = [Some(id), card.name.as_deref()]
+                .into_iter()
+                .flatten()
+                .filter_map(|key| self.lookup.get(&key.to_lowercase()))
+                .collect::<Vec<_>>();
+            for &index in &matches {
+                found[*index] = true;
+            }
+            !matches.is_empty()
+        });
+    }
+
+    /// Entries that matched no card in any source processed so far
+    pub fn missing(&self) -> Vec<&str> {
+        let found = self.found.lock().unwrap_or_else(|e| e.into_inner());
+        self.entries
+            .iter()
+            .zip(found.iter())
+            .filter(|(_, found)| !**found)
+            .map(|(entry, _)| entry.as_str())
+            .collect()
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, SemaphorePermit};
+
+/// Concurrency the adaptive limiter starts from before ramping up
+const INITIAL_ADAPTIVE_LIMIT: usize = 4;
+
+/// Minimum time between two multiplicative decreases
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// Requests slower than this multiple of the average latency count as congestion
+const SLOW_LATENCY_FACTOR: f64 = 2.0;
+
+/// Resolve a requested worker count, treating 0 as one worker per CPU core
+///
+/// A zero-permit semaphore or thread pool would never make progress, so 0 is
+/// never passed through.
+pub fn resolve_thread_count(requested: usize) -> usize {
+    if requested > 0 {
+        return requested;
+    }
+
+    std::thread::available_parallelism().map_or(1, |cores| cores.get())
+}
+
+struct LimiterState {
+    limit: usize,
+    pending_reductions: usize,
+    successes_since_increase: usize,
+    average_latency: Option<f64>,
+    last_decrease: Option<Instant>,
+}
+
+/// Additive-increase/multiplicative-decrease concurrency limiter
+///
+/// The limit grows by one after a full window of fast, successful requests
+/// and halves when a request fails or is much slower than average.
+pub struct AdaptiveLimiter {
+    semaphore: Semaphore,
+    state: Mutex<LimiterState>,
+    max: usize,
+}
+
+impl AdaptiveLimiter {
+    /// Create a limiter that never exceeds `max` concurrent requests
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        let initial = INITIAL_ADAPTIVE_LIMIT.min(max);
+
+        Self {
+            semaphore: Semaphore::new(initial),
+            state: Mutex::new(LimiterState {
+                limit: initial,
+                pending_reductions: 0,
+                successes_since_increase: 0,
+                average_latency: None,
+                last_decrease: None,
+            }),
+            max,
+        }
+    }
+
+    /// Wait for a slot; every call must be paired with [`AdaptiveLimiter::release`]
+    pub async fn acquire(&self) {
+        if let Ok(permit) = self.semaphore.acquire().await {
+            permit.forget();
+        }
+    }
+
+    /// Return a slot and adjust the limit based on how the request went
+    pub fn release(&self, latency: Duration, success: bool) {
+        let Ok(mut state) = self.state.lock() else {
+            self.semaphore.add_permits(1);
+            return;
+        };
+
+        let latency = latency.as_secs_f64();
+        let slow = state
+            .average_latency
+            .is_some_and(|average| latency > average * SLOW_LATENCY_FACTOR);
+
+        if success {
+            state.average_latency = Some(match state.average_latency {
+                Some(average) => average * 0.9 + latency * 0.1,
+                None => latency,
+            });
+        }
+
+        // Give the slot back unless the limit was lowered while it was in use
+        if state.pending_reductions > 0 {
+            state.pending_reductions -= 1;
+        } else {
+            self.semaphore.add_permits(1);
+        }
+
+        if !success || slow {
+            let cooled_down = state
+                .last_decrease
+                .is_none_or(|at| at.elapsed() >= DECREASE_COOLDOWN);
+            if cooled_down && state.limit > 1 {
+                let new_limit = (state.limit / 2).max(1);
+                state.pending_reductions += state.limit - new_limit;
+                state.limit = new_limit;
+                state.last_decrease = Some(Instant::now());
+            }
+            state.successes_since_increase = 0;
+            return;
+        }
+
+        state.successes_since_increase += 1;
+        if state.successes_since_increase >= state.limit && state.limit < self.max {
+            state.successes_since_increase = 0;
+            state.limit += 1;
+            if state.pending_reductions > 0 {
+                state.pending_reductions -= 1;
+            } else {
+                self.semaphore.add_permits(1);
+            }
+        }
+    }
+
+    /// Current concurrency limit
+    pub fn limit(&self) -> usize {
+        self.state.lock().map_or(0, |state| state.limit)
+    }
+}
+
+/// Caps concurrent requests to each host, on top of the overall download limit
+///
+/// Hosts are keyed by the URL's host name, so the primary image CDN and a
+/// fallback mirror are limited independently.
+pub struct HostLimiter {
+    per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    /// Create a limiter allowing `per_host` concurrent requests to any one host
+    pub fn new(per_host: usize) -> Self {
+        Self {
+            per_host: per_host.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for a slot on the host of `url`, held until the returned permit is dropped
+    ///
+    /// URLs without a host are not limited.
+    pub async fn acquire(&self, url: &str) -> Option<OwnedSemaphorePermit> {
+        let host = reqwest::Url::parse(url).ok()?.host_str()?.to_string();
+        let semaphore = {
+            let mut hosts = self.hosts.lock().ok()?;
+            hosts
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.per_host)))
+                .clone()
+        };
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+/// Bounds the estimated memory of images being decoded and processed at once
+///
+/// Reservations are counted in KiB. An image estimated to need more than the
+/// whole budget reserves all of it, so it runs alone rather than never.
+pub struct MemoryBudget {
+    semaphore: Semaphore,
+    total_kib: u32,
+}
+
+impl MemoryBudget {
+    /// Create a budget of `max_bytes`
+    pub fn new(max_bytes: u64) -> Self {
+        let total_kib = (max_bytes / 1024)
+            .clamp(1, u32::MAX as u64)
+            .min(Semaphore::MAX_PERMITS as u64) as u32;
+
+        Self {
+            semaphore: Semaphore::new(total_kib as usize),
+            total_kib,
+        }
+    }
+
+    /// Wait until `bytes` fit in the budget, held until the returned permit is dropped
+    pub async fn reserve(&self, bytes: u64) -> Option<SemaphorePermit<'_>> {
+        let kib = (bytes.div_ceil(1024)).clamp(1, self.total_kib as u64) as u32;
+        self.semaphore.acquire_many(kib).await.ok()
+    }
+}
+
+/// Gap enforced between requests to a host when it first answers 429
+const INITIAL_THROTTLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Longest gap between requests to a throttled host
+const MAX_THROTTLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Gaps shorter than this are dropped and the host is no longer throttled
+const MIN_THROTTLE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a host must go without a 429 before its rate starts recovering
+const THROTTLE_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Time between steps of the recovery, each halving the gap
+const THROTTLE_RESTORE_STEP: Duration = Duration::from_secs(10);
+
+struct HostRate {
+    interval: Duration,
+    next_request: Instant,
+    next_restore: Instant,
+}
+
+/// Spaces out requests to hosts that answer 429, recovering once they stop
+///
+/// A 429 starts (or doubles) a minimum gap between requests to that host. After
+/// a cooldown without further 429s the gap halves step by step until the host
+/// is unthrottled again. Engaging and releasing a throttle is logged.
+pub struct HostThrottle {
+    hosts: Mutex<HashMap<String, HostRate>>,
+    log: Box<dyn Fn(String) + Send + Sync>,
+}
+
+impl HostThrottle {
+    /// Create a throttle reporting changes through `log`
+    pub fn new(log: impl Fn(String) + Send + Sync + 'static) -> Self {
+        Self {
+            hosts: Mutex::new(HashMap::new()),
+            log: Box::new(log),
+        }
+    }
+
+    fn host(url: &str) -> Option<String> {
+        Some(reqwest::Url::parse(url).ok()?.host_str()?.to_string())
+    }
+
+    /// Wait until a request to the host of `url` is allowed
+    pub async fn wait(&self, url: &str) {
+        let Some(host) = Self::host(url) else {
+            return;
+        };
+        let start_at = {
+            let Ok(mut hosts) = self.hosts.lock() else {
+                return;
+            };
+            let Some(rate) = hosts.get_mut(&host) else {
+                return;
+            };
+            let start_at = rate.next_request.max(Instant::now());
+            rate.next_request = start_at + rate.interval;
+            start_at
+        };
+        tokio::time::sleep_until(start_at.into()).await;
+    }
+
+    /// Adjust the host's rate after a response, `throttled` meaning it answered 429
+    pub fn record(&self, url: &str, throttled: bool) {
+        let Some(host) = Self::host(url) else {
+            return;
+        };
+        let Ok(mut hosts) = self.hosts.lock() else {
+            return;
+        };
+        let now = Instant::now();
+
+        if throttled {
+            match hosts.get_mut(&host) {
+                Some(rate) => {
+                    rate.interval = (rate.interval * 2).min(MAX_THROTTLE_INTERVAL);
+                    rate.next_restore = now + THROTTLE_COOLDOWN;
+                }
+                None => {
+                    (self.log)(format!(
+                        "{} is rate limiting requests, slowing to one every {}ms",
+                        host,
+                        INITIAL_THROTTLE_INTERVAL.as_millis()
+                    ));
+                    hosts.insert(
+                        host,
+                        HostRate {
+                            interval: INITIAL_THROTTLE_INTERVAL,
+                            next_request: now + INITIAL_THROTTLE_INTERVAL,
+                            next_restore: now + THROTTLE_COOLDOWN,
+                        },
+                    );
+                }
+            }
+            return;
+        }
+
+        let Some(rate) = hosts.get_mut(&host) else {
+            return;
+        };
+        if now < rate.next_restore {
+            return;
+        }
+        rate.interval /= 2;
+        rate.next_restore = now + THROTTLE_RESTORE_STEP;
+        if rate.interval < MIN_THROTTLE_INTERVAL {
+            hosts.remove(&host);
+            (self.log)(format!("{} recovered, no longer throttling requests", host));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cores() -> usize {
+        std::thread::available_parallelism().map_or(1, |cores| cores.get())
+    }
+
+    #[test]
+    fn zero_threads_uses_every_core() {
+        assert_eq!(resolve_thread_count(0), cores());
+        assert!(resolve_thread_count(0) >= 1);
+    }
+
+    #[test]
+    fn one_thread_is_kept() {
+        assert_eq!(resolve_thread_count(1), 1);
+    }
+
+    #[test]
+    fn counts_above_the_core_count_are_kept() {
+        let requested = cores() * 4 + 1;
+        assert_eq!(resolve_thread_count(requested), requested);
+    }
+}
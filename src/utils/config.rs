@@ -0,0 +1,91 @@
+use clap::Command;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Read a TOML config file and turn one subcommand's table into command line arguments
+///
+/// The file has one table per subcommand (`[fetch]`, `[augment]`, `[clean]`) whose keys
+/// are option names in snake_case, e.g. `max_image_dimension = 1200`. Booleans enable
+/// flags and arrays repeat an option. The returned arguments go before the user's own
+/// so values given on the command line take precedence over the file.
+pub fn config_args(path: &Path, command: &Command, subcommand: &str) -> io::Result<Vec<OsString>> {
+    let invalid = |message: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid config file {}: {}", path.display(), message),
+        )
+    };
+
+    let content = fs::read_to_string(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to read config file {}: {}", path.display(), e),
+        )
+    })?;
+    let table: toml::Table = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+
+    for key in table.keys() {
+        if command.find_subcommand(key).is_none() {
+            return Err(invalid(format!("unknown section '{}'", key)));
+        }
+    }
+
+    let Some(section) = table.get(subcommand) else {
+        return Ok(Vec::new());
+    };
+    let section = section
+        .as_table()
+        .ok_or_else(|| invalid(format!("'{}' must be a table", subcommand)))?;
+    let subcommand_args = command
+        .find_subcommand(subcommand)
+        .ok_or_else(|| invalid(format!("unknown section '{}'", subcommand)))?;
+
+    let mut args = Vec::new();
+    for (key, value) in section {
+        let arg = subcommand_args
+            .get_arguments()
+            .find(|arg| arg.get_id() == key.as_str() && key != "config")
+            .and_then(|arg| arg.get_long().map(|long| (arg, long)));
+        let Some((arg, long)) = arg else {
+            return Err(invalid(format!("unknown option '{}.{}'", subcommand, key)));
+        };
+        let flag = OsString::from(format!("--{}", long));
+        let takes_value = arg.get_action().takes_values();
+
+        let values = match value {
+            toml::Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        };
+        for value in values {
+            match value {
+                toml::Value::Boolean(enabled) if !takes_value => {
+                    if *enabled {
+                        args.push(flag.clone());
+                    }
+                }
+                toml::Value::String(text) if takes_value => {
+                    args.push(flag.clone());
+                    args.push(text.into());
+                }
+                toml::Value::Integer(number) if takes_value => {
+                    args.push(flag.clone());
+                    args.push(number.to_string().into());
+                }
+                toml::Value::Float(number) if takes_value => {
+                    args.push(flag.clone());
+                    args.push(number.to_string().into());
+                }
+                _ => {
+                    return Err(invalid(format!(
+                        "unsupported value for '{}.{}': {}",
+                        subcommand, key, value
+                    )))
+                }
+            }
+        }
+    }
+
+    Ok(args)
+}
@@ -1,8 +1,10 @@
 use crate::tcg::TcgType;
 use rayon::prelude::*;
+use std::collections::HashSet;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 /// Ensure required directories exist for TCG data storage
 pub fn ensure_directories(base_path: &str) -> io::Result<()> {
@@ -33,19 +35,106 @@ pub fn ensure_directories(base_path: &str) -> io::Result<()> {
     Ok(())
 }
 
+/// Parse a duration such as `30d`, `12h`, `45m` or `90s` (plain numbers are seconds)
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit_seconds) = match value.char_indices().last() {
+        Some((index, 'd')) => (&value[..index], 24 * 60 * 60),
+        Some((index, 'h')) => (&value[..index], 60 * 60),
+        Some((index, 'm')) => (&value[..index], 60),
+        Some((index, 's')) => (&value[..index], 1),
+        _ => (value, 1),
+    };
+
+    let invalid = || {
+        format!(
+            "Invalid duration '{}', expected a number with an optional d, h, m or s suffix",
+            value
+        )
+    };
+
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    number
+        .checked_mul(unit_seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration '{}' is too long", value))
+}
+
+/// Parse a byte size such as `512M`, `2G` or `64K` (plain numbers are bytes)
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "Invalid size '{}', expected a number with an optional K, M or G suffix",
+            value
+        )
+    };
+
+    let trimmed = value.trim().to_uppercase();
+    let trimmed = trimmed.strip_suffix('B').unwrap_or(&trimmed);
+    let (number, unit) = match trimmed.char_indices().last() {
+        Some((index, 'K')) => (&trimmed[..index], 1 << 10),
+        Some((index, 'M')) => (&trimmed[..index], 1 << 20),
+        Some((index, 'G')) => (&trimmed[..index], 1 << 30),
+        _ => (trimmed, 1),
+    };
+
+    let bytes = number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit))
+        .ok_or_else(invalid)?;
+    if bytes == 0 {
+        return Err(format!("Size must be greater than zero, got '{}'", value));
+    }
+    Ok(bytes)
+}
+
+/// Whether a cached file is recent enough to reuse, printing a note when it is stale
+///
+/// Without `max_age` every existing file is fresh; a zero `max_age` forces a refetch.
+pub fn is_fresh(path: &Path, max_age: Option<Duration>) -> bool {
+    let Some(max_age) = max_age else {
+        return path.exists();
+    };
+    if max_age.is_zero() {
+        return false;
+    }
+
+    let age = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or(Duration::ZERO)
+        });
+    match age {
+        Ok(age) if age <= max_age => true,
+        Ok(age) => {
+            println!(
+                "{} is {:.1} hours old, older than --max-age; fetching fresh data",
+                path.display(),
+                age.as_secs_f64() / 3600.0
+            );
+            false
+        }
+        Err(_) => false,
+    }
+}
+
 /// Check for existing JSON files for a specific TCG type
-pub fn check_json_files(directory: &str, tcg_type: &TcgType) -> Vec<String> {
+///
+/// Files older than `max_age` are treated as missing so they get fetched again.
+pub fn check_json_files(
+    directory: &str,
+    tcg_type: &TcgType,
+    max_age: Option<Duration>,
+) -> Vec<String> {
     let base_path = Path::new(directory);
     let mut existing_files = Vec::new();
 
-    // Check for the specific TCG type file
-    let file_type = match tcg_type {
-        TcgType::Mtg => "mtg_cards",
-        TcgType::Ga => "ga_cards",
-    };
-
-    let file_path = base_path.join(format!("{}.json", file_type));
-    if file_path.exists() {
+    let file_path = base_path.join(format!("{}.json", tcg_type.source().json_file_stem()));
+    if is_fresh(&file_path, max_age) {
         existing_files.push(file_path.to_string_lossy().into_owned());
     }
 
@@ -53,7 +142,10 @@ pub fn check_json_files(directory: &str, tcg_type: &TcgType) -> Vec<String> {
 }
 
 /// Count and display the number of directories in the train folder
-pub fn count_train_directories(base_path: &str) -> io::Result<()> {
+///
+/// When `group_by_set` is enabled card directories live one level deeper,
+/// under `data/train/<set-code>/`, so those are counted instead.
+pub fn count_train_directories(base_path: &str, group_by_set: bool) -> io::Result<()> {
     let train_path = Path::new(base_path).join("data/train");
 
     if !train_path.exists() {
@@ -61,20 +153,205 @@ pub fn count_train_directories(base_path: &str) -> io::Result<()> {
         return Ok(());
     }
 
-    let count = fs::read_dir(&train_path)?
+    let top_level = list_subdirectories(&train_path)?;
+
+    if !group_by_set {
+        println!(
+            "Total card directories in train folder: {}",
+            top_level.len()
+        );
+        return Ok(());
+    }
+
+    let count: usize = top_level
+        .par_iter()
+        .map(|set_dir| list_subdirectories(set_dir).map_or(0, |dirs| dirs.len()))
+        .sum();
+
+    println!(
+        "Total card directories in train folder: {} across {} sets",
+        count,
+        top_level.len()
+    );
+    Ok(())
+}
+
+/// List card directories in the train folder, relative to `data/train`
+pub fn list_card_directories(base_path: &str, group_by_set: bool) -> io::Result<Vec<PathBuf>> {
+    let train_path = Path::new(base_path).join("data/train");
+
+    if !train_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let top_level = list_subdirectories(&train_path)?;
+    let card_dirs: Vec<PathBuf> = if group_by_set {
+        top_level
+            .par_iter()
+            .flat_map(|set_dir| list_subdirectories(set_dir).unwrap_or_default())
+            .collect()
+    } else {
+        top_level
+    };
+
+    Ok(card_dirs
+        .into_iter()
+        .filter_map(|dir| dir.strip_prefix(&train_path).ok().map(Path::to_path_buf))
+        .collect())
+}
+
+/// Remove card directories that are no longer present in the source data
+///
+/// When `trash` is set the directories are moved to `<base>/.trash/` instead
+/// of being deleted. Returns the number of directories removed.
+pub fn remove_card_directories(
+    base_path: &str,
+    relative_dirs: &[PathBuf],
+    trash: bool,
+) -> io::Result<usize> {
+    let train_path = Path::new(base_path).join("data/train");
+    let trash_path = Path::new(base_path).join(".trash");
+
+    let removed = relative_dirs
+        .par_iter()
+        .map(|relative| -> io::Result<()> {
+            let source = train_path.join(relative);
+            if trash {
+                let target = trash_path.join(relative);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&source, &target)?;
+            } else {
+                fs::remove_dir_all(&source)?;
+            }
+            println!("Removed extraneous card directory: {}", relative.display());
+            Ok(())
+        })
+        .filter(|result| match result {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Failed to remove card directory: {}", e);
+                false
+            }
+        })
+        .count();
+
+    Ok(removed)
+}
+
+/// Counts of incomplete-run artifacts found (or removed) by [`clean_train_directory`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CleanStats {
+    pub temp_files: usize,
+    pub empty_dirs: usize,
+}
+
+/// Find stray `temp.*` files and empty card directories left by failed runs
+///
+/// Nothing is deleted unless `apply` is set; otherwise the artifacts that
+/// would be removed are only reported.
+pub fn clean_train_directory(base_path: &str, apply: bool) -> io::Result<CleanStats> {
+    let train_path = Path::new(base_path).join("data/train");
+
+    if !train_path.exists() {
+        println!("Train directory does not exist yet.");
+        return Ok(CleanStats::default());
+    }
+
+    let stats = list_subdirectories(&train_path)?
+        .par_iter()
+        .map(|dir| {
+            let mut stats = CleanStats::default();
+            if let Err(e) = clean_directory(dir, apply, &mut stats) {
+                eprintln!("Failed to clean {}: {}", dir.display(), e);
+            }
+            stats
+        })
+        .reduce(CleanStats::default, |a, b| CleanStats {
+            temp_files: a.temp_files + b.temp_files,
+            empty_dirs: a.empty_dirs + b.empty_dirs,
+        });
+
+    Ok(stats)
+}
+
+/// Recursively clean a directory, returning whether it is (or would be) empty
+fn clean_directory(dir: &Path, apply: bool, stats: &mut CleanStats) -> io::Result<bool> {
+    let mut remaining = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            if !clean_directory(&path, apply, stats)? {
+                remaining += 1;
+            }
+        } else if is_temp_file(&path) {
+            stats.temp_files += 1;
+            if apply {
+                fs::remove_file(&path)?;
+                println!("Removed temp file: {}", path.display());
+            } else {
+                println!("Would remove temp file: {}", path.display());
+            }
+        } else {
+            remaining += 1;
+        }
+    }
+
+    if remaining > 0 {
+        return Ok(false);
+    }
+
+    stats.empty_dirs += 1;
+    if apply {
+        fs::remove_dir(dir)?;
+        println!("Removed empty directory: {}", dir.display());
+    } else {
+        println!("Would remove empty directory: {}", dir.display());
+    }
+    Ok(true)
+}
+
+/// Check whether a file is a leftover download temp file (`temp.png`, `temp.jpg`, ...)
+fn is_temp_file(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem == "temp" || stem.ends_with(".temp"))
+}
+
+/// Names of the set directories under `data/train` that hold at least one card
+///
+/// Only meaningful for the `--group-by-set` layout.
+pub fn list_set_directories(base_path: &str) -> io::Result<HashSet<String>> {
+    let train_path = Path::new(base_path).join("data/train");
+
+    if !train_path.exists() {
+        return Ok(HashSet::new());
+    }
+
+    Ok(list_subdirectories(&train_path)?
+        .into_iter()
+        .filter(|set_dir| list_subdirectories(set_dir).is_ok_and(|cards| !cards.is_empty()))
+        .filter_map(|set_dir| Some(set_dir.file_name()?.to_string_lossy().into_owned()))
+        .collect())
+}
+
+/// List the immediate subdirectories of a directory
+fn list_subdirectories(path: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(fs::read_dir(path)?
         .filter_map(|entry| {
             entry.ok().and_then(|e| {
                 if e.file_type().ok()?.is_dir() {
-                    Some(())
+                    Some(e.path())
                 } else {
                     None
                 }
             })
         })
-        .count();
-
-    println!("Total card directories in train folder: {}", count);
-    Ok(())
+        .collect())
 }
 
 // TODO: Add tests with proper test dependencies
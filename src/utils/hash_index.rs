@@ -0,0 +1,86 @@
+use crate::utils::files::list_card_directories;
+use crate::utils::images::open_image;
+use image::imageops::FilterType;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Compute a 64-bit difference hash (dHash) of an image
+///
+/// The image is shrunk to 9x8 grayscale and each bit records whether a pixel
+/// is brighter than its right-hand neighbour, so visually similar images end
+/// up with hashes that differ in only a few bits.
+pub fn difference_hash(image_path: &Path) -> io::Result<u64> {
+    let img = open_image(image_path)?;
+    let small = image::imageops::resize(&img.to_luma8(), 9, 8, FilterType::Triangle);
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+
+    Ok(hash)
+}
+
+/// Write a `card_id -> dHash` index for every downloaded card image
+///
+/// The output format is chosen from the file extension: `.csv` writes CSV,
+/// anything else writes a JSON object.
+pub fn write_hash_index(
+    base_path: &str,
+    group_by_set: bool,
+    image_name: &str,
+    output: &Path,
+) -> io::Result<usize> {
+    let train_path = Path::new(base_path).join("data/train");
+    let card_dirs = list_card_directories(base_path, group_by_set)?;
+
+    let hashes: BTreeMap<String, String> = card_dirs
+        .par_iter()
+        .filter_map(|relative| {
+            let image_path = train_path.join(relative).join(image_name);
+            if !image_path.exists() {
+                return None;
+            }
+            match difference_hash(&image_path) {
+                Ok(hash) => {
+                    let card_id = relative.file_name()?.to_string_lossy().into_owned();
+                    Some((card_id, format!("{:016x}", hash)))
+                }
+                Err(e) => {
+                    eprintln!("Failed to hash {}: {}", image_path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let is_csv = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+    let contents = if is_csv {
+        let mut csv = String::from("card_id,hash\n");
+        for (card_id, hash) in &hashes {
+            csv.push_str(&format!("{},{}\n", card_id, hash));
+        }
+        csv
+    } else {
+        serde_json::to_string_pretty(&hashes)?
+    };
+
+    fs::write(output, contents)?;
+    println!(
+        "Wrote perceptual hash index for {} cards to: {}",
+        hashes.len(),
+        output.display()
+    );
+
+    Ok(hashes.len())
+}
@@ -1,39 +1,335 @@
+use crate::utils::concurrency::HostThrottle;
 use reqwest;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_RANGE, ETAG, IF_RANGE, RANGE, RETRY_AFTER,
+};
+use reqwest::redirect::Policy;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
 use std::io;
 use std::path::Path;
+use std::time::{Duration, SystemTime};
 use tokio;
+use tokio::io::AsyncWriteExt;
+
+/// Maximum number of times a throttled request is retried
+const MAX_THROTTLE_RETRIES: u32 = 5;
+
+/// Wait used when a throttled response has no usable `Retry-After` header
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Longest `Retry-After` honoured; asking for more fails the request instead of stalling the run
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Settings for the HTTP client shared by every request in a run
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    /// Extra headers attached to all requests (cookies, bearer tokens, referers, ...)
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// Maximum idle connections kept per host (reqwest's default when unset)
+    pub pool_size: Option<usize>,
+    /// Negotiate HTTP/2 via ALPN, falling back to HTTP/1.1 for servers without it
+    pub http2: bool,
+    /// Maximum redirects followed per request, 0 to not follow any (reqwest's default when unset)
+    pub max_redirects: Option<usize>,
+}
+
+/// Parse a `Name: Value` header argument
+pub fn parse_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, header_value) = value
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid header '{}', expected 'Name: Value'", value))?;
+
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|e| format!("Invalid header name in '{}': {}", value, e))?;
+    let header_value = HeaderValue::from_str(header_value.trim())
+        .map_err(|e| format!("Invalid header value in '{}': {}", value, e))?;
+
+    Ok((name, header_value))
+}
+
+/// Build the HTTP client shared by all requests
+pub fn build_client(options: &HttpOptions) -> io::Result<reqwest::Client> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &options.headers {
+        headers.append(name.clone(), value.clone());
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .user_agent(get_user_agent())
+        .default_headers(headers);
+    if let Some(pool_size) = options.pool_size {
+        builder = builder.pool_max_idle_per_host(pool_size);
+    }
+    if let Some(max_redirects) = options.max_redirects {
+        builder = builder.redirect(if max_redirects == 0 {
+            Policy::none()
+        } else {
+            Policy::limited(max_redirects)
+        });
+    }
+    builder = if options.http2 {
+        builder.http2_adaptive_window(true)
+    } else {
+        builder.http1_only()
+    };
+
+    builder
+        .build()
+        .map_err(|e| io::Error::other(format!("Failed to build HTTP client: {}", e)))
+}
+
+/// POST a JSON payload to a notification endpoint
+pub async fn post_notification<T: Serialize>(
+    client: &reqwest::Client,
+    url: &str,
+    payload: &T,
+) -> io::Result<()> {
+    let response = client
+        .post(url)
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| io::Error::other(format!("Failed to send notification: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(io::Error::other(format!(
+            "Notification endpoint returned status {}",
+            response.status()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Where a `.part` file left by an interrupted bulk download came from
+#[derive(Debug, Serialize, Deserialize)]
+struct PartialDownload {
+    /// Download URI the partial file holds the start of
+    uri: String,
+    /// ETag of that download, sent as `If-Range` when resuming
+    etag: String,
+}
+
+/// Remove a file, treating one that is already gone as removed
+async fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// The first byte and total length of a `Content-Range: bytes <start>-<end>/<total>` header
+fn parse_content_range(headers: &HeaderMap) -> Option<(u64, Option<u64>)> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-')?.0.trim().parse().ok()?;
+    Some((start, total.trim().parse().ok()))
+}
 
 /// Download JSON data from a URL and save it to a local file
+///
+/// The body is streamed into `<data_type>.json.part` and renamed once its size
+/// matches what the server announced. The download URI and ETag are saved next
+/// to it, so a `.part` file left by an interrupted run is resumed with a `Range`
+/// request only when it came from the same URI and the server sent an ETag;
+/// `If-Range` then makes the server send the whole file again if it changed.
+/// Without a matching validator the download starts over, since appending to
+/// a rotated bulk file would produce corrupt JSON.
 pub async fn download_json_data(
+    client: &reqwest::Client,
     data_type: &str,
     download_uri: &str,
     directory: &str,
 ) -> io::Result<String> {
-    let client = reqwest::Client::new();
     let file_path = Path::new(directory).join(format!("{}.json", data_type));
+    let part_path = Path::new(directory).join(format!("{}.json.part", data_type));
+    let meta_path = Path::new(directory).join(format!("{}.json.part.meta", data_type));
 
     println!("Downloading {} data...", data_type);
 
-    let response = client
-        .get(download_uri)
-        .header("User-Agent", get_user_agent())
-        .send()
-        .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    let (mut response, resume_from) = loop {
+        let partial = tokio::fs::read_to_string(&meta_path)
+            .await
+            .ok()
+            .and_then(|content| serde_json::from_str::<PartialDownload>(&content).ok())
+            .filter(|partial| partial.uri == download_uri);
+        let resume_from = match &partial {
+            Some(_) => tokio::fs::metadata(&part_path)
+                .await
+                .map_or(0, |metadata| metadata.len()),
+            None => 0,
+        };
+
+        let mut request = client.get(download_uri);
+        match &partial {
+            Some(partial) if resume_from > 0 => {
+                request = request
+                    .header(RANGE, format!("bytes={}-", resume_from))
+                    .header(IF_RANGE, partial.etag.as_str());
+            }
+            // Nothing to resume from, so a leftover partial file is of no use
+            _ => {
+                remove_if_exists(&part_path).await?;
+                remove_if_exists(&meta_path).await?;
+            }
+        }
 
-    let bytes = response
-        .bytes()
+        let response = request
+            .send()
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        // The partial file is stale or already complete; start over
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
+            remove_if_exists(&part_path).await?;
+            remove_if_exists(&meta_path).await?;
+            continue;
+        }
+        // Only append a range that starts exactly where the partial file ends
+        if response.status() == StatusCode::PARTIAL_CONTENT
+            && parse_content_range(response.headers()).map(|(start, _)| start) != Some(resume_from)
+        {
+            remove_if_exists(&part_path).await?;
+            remove_if_exists(&meta_path).await?;
+            if resume_from == 0 {
+                return Err(io::Error::other(format!(
+                    "Unexpected partial response for URL: {}",
+                    download_uri
+                )));
+            }
+            continue;
+        }
+        break (response, resume_from);
+    };
+
+    if !response.status().is_success() {
+        return Err(io::Error::other(format!(
+            "HTTP {} for URL: {}",
+            response.status(),
+            download_uri
+        )));
+    }
+
+    let resuming = response.status() == StatusCode::PARTIAL_CONTENT && resume_from > 0;
+    let expected_len = if resuming {
+        parse_content_range(response.headers()).and_then(|(_, total)| total)
+    } else {
+        response.content_length()
+    };
+
+    let mut file = if resuming {
+        println!("Resuming download from {}", part_path.display());
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&part_path)
+            .await?
+    } else {
+        match response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+        {
+            Some(etag) => {
+                let partial = PartialDownload {
+                    uri: download_uri.to_string(),
+                    etag: etag.to_string(),
+                };
+                tokio::fs::write(&meta_path, serde_json::to_string(&partial)?).await?
+            }
+            None => remove_if_exists(&meta_path).await?,
+        }
+        tokio::fs::File::create(&part_path).await?
+    };
+
+    while let Some(chunk) = response
+        .chunk()
         .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        .map_err(|e| io::Error::other(format!("Download interrupted, rerun to resume: {}", e)))?
+    {
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| io::Error::other(format!("Failed to write file: {}", e)))?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    let actual_len = tokio::fs::metadata(&part_path).await?.len();
+    if let Some(expected_len) = expected_len {
+        if actual_len != expected_len {
+            return Err(io::Error::other(format!(
+                "Downloaded {} of {} bytes for {}, rerun to resume",
+                actual_len, expected_len, data_type
+            )));
+        }
+    }
 
-    tokio::fs::write(&file_path, &bytes).await.map_err(|e| {
-        io::Error::new(io::ErrorKind::Other, format!("Failed to write file: {}", e))
-    })?;
+    tokio::fs::rename(&part_path, &file_path).await?;
+    remove_if_exists(&meta_path).await?;
 
     println!("Successfully downloaded: {}", file_path.display());
     Ok(file_path.to_string_lossy().into_owned())
 }
 
+/// Parse a `Retry-After` header given either as seconds or as an HTTP date
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Check whether a response indicates the server is throttling us
+fn is_throttled(status: StatusCode, headers: &HeaderMap) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::SERVICE_UNAVAILABLE && headers.contains_key(RETRY_AFTER))
+}
+
+/// Send a GET request, sleeping and retrying when the server responds with 429
+/// (or 503 with `Retry-After`) instead of treating it as a permanent failure
+///
+/// A `Retry-After` longer than [`MAX_RETRY_AFTER`] is not waited out: the
+/// throttled response is returned so the caller records the failure.
+///
+/// With a `throttle`, every attempt waits for the host's turn and reports
+/// whether it was throttled, so later requests to that host slow down too.
+pub async fn get_with_rate_limit_retry(
+    client: &reqwest::Client,
+    url: &str,
+    throttle: Option<&HostThrottle>,
+) -> reqwest::Result<reqwest::Response> {
+    let mut attempt = 0;
+
+    loop {
+        if let Some(throttle) = throttle {
+            throttle.wait(url).await;
+        }
+        let response = client.get(url).send().await?;
+        let throttled = is_throttled(response.status(), response.headers());
+        if let Some(throttle) = throttle {
+            throttle.record(url, throttled);
+        }
+
+        if !throttled || attempt >= MAX_THROTTLE_RETRIES {
+            return Ok(response);
+        }
+
+        let wait = parse_retry_after(response.headers()).unwrap_or(DEFAULT_RETRY_AFTER);
+        if wait > MAX_RETRY_AFTER {
+            return Ok(response);
+        }
+        attempt += 1;
+        tokio::time::sleep(wait).await;
+    }
+}
+
 /// Get standard user agent string
 pub fn get_user_agent() -> &'static str {
     "TCGFetch"
@@ -1,19 +1,146 @@
-use crate::tcg::{TcgType, UnifiedCard};
-use crate::utils::http::get_user_agent;
+use crate::tcg::mtg::{Finish, ImageStatus};
+use crate::tcg::{TcgSource, TcgType, UnifiedCard};
+use crate::utils::cache::ImageCache;
+use crate::utils::card_list::CardList;
+use crate::utils::concurrency::{
+    resolve_thread_count, AdaptiveLimiter, HostLimiter, HostThrottle, MemoryBudget,
+};
+use crate::utils::http::get_with_rate_limit_retry;
+use crate::utils::manifest::{ManifestRecord, ManifestWriter};
+use crate::utils::parse_cache::load_cards;
+use crate::utils::progress::{ProgressCounters, ProgressReporter};
+use crate::utils::schedule::{Schedule, ScheduleGate};
+use crate::utils::state::{Checkpoint, FailedDownload};
+use crate::utils::storage::{ImageStore, LocalStore};
+use crate::utils::template::{sanitize_component, OutputTemplate};
+use bytes::Bytes;
+use clap::ValueEnum;
 use futures::stream::StreamExt;
-use image::GenericImageView;
-use indicatif::{ProgressBar, ProgressStyle};
+use image::codecs::gif::GifDecoder;
+use image::{
+    AnimationDecoder, DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader,
+};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use reqwest;
-use serde_json;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
-use std::sync::{atomic::AtomicUsize, Arc};
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+/// Open an image, detecting its format from the file contents rather than the extension
+///
+/// Animated GIFs are reduced to their first frame on purpose; a note is printed
+/// so the dropped frames don't go unnoticed.
+pub fn open_image(path: &Path) -> io::Result<DynamicImage> {
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    if reader.format() != Some(ImageFormat::Gif) {
+        return reader.decode().map_err(io::Error::other);
+    }
+
+    let decoder =
+        GifDecoder::new(io::BufReader::new(fs::File::open(path)?)).map_err(io::Error::other)?;
+    let mut frames = decoder.into_frames();
+    let first = frames
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "GIF has no frames"))?
+        .map_err(io::Error::other)?;
+    if frames.next().is_some() {
+        eprintln!(
+            "Note: {} is animated, using its first frame",
+            path.display()
+        );
+    }
+
+    Ok(DynamicImage::ImageRgba8(first.into_buffer()))
+}
+
+/// Dimensions and size of an image that passed validation
+#[derive(Debug, Clone, Copy)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub file_size: u64,
+    /// Whether processing cropped the source to a detected card boundary
+    pub auto_cropped: bool,
+}
+
+/// Width of processed images unless `--width` is given
+pub const DEFAULT_WIDTH: u32 = 500;
+
+/// Height of processed images unless `--height` is given
+pub const DEFAULT_HEIGHT: u32 = 700;
+
+/// Smallest width or height a valid image may have
+pub const MIN_IMAGE_DIMENSION: u32 = 10;
+
+/// Largest width or height a valid image may have
+pub const MAX_IMAGE_DIMENSION: u32 = 10000;
+
+/// Parse a `--width`/`--height` value, enforcing the bounds `validate_image` checks
+pub fn parse_image_dimension(value: &str) -> Result<u32, String> {
+    let dimension: u32 = value.trim().parse().map_err(|_| {
+        format!(
+            "Invalid image dimension '{}', expected a number of pixels",
+            value
+        )
+    })?;
+
+    if !(MIN_IMAGE_DIMENSION..=MAX_IMAGE_DIMENSION).contains(&dimension) {
+        return Err(format!(
+            "Image dimension must be between {} and {} pixels, got {}",
+            MIN_IMAGE_DIMENSION, MAX_IMAGE_DIMENSION, dimension
+        ));
+    }
+
+    Ok(dimension)
+}
+
+/// Parse one `--sizes` entry, e.g. `224x224`
+pub fn parse_image_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .trim()
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("Invalid image size '{}', expected WIDTHxHEIGHT", value))?;
+    Ok((
+        parse_image_dimension(width)?,
+        parse_image_dimension(height)?,
+    ))
+}
+
+/// Largest unsharp-mask radius accepted by `--sharpen`, beyond which halos dominate
+pub const MAX_SHARPEN: f32 = 5.0;
+
+/// Brightness difference below which `--sharpen` leaves pixels alone, so flat areas
+/// and JPEG noise are not amplified
+const SHARPEN_THRESHOLD: i32 = 2;
+
+/// Parse a `--sharpen` amount, the unsharp-mask radius in pixels
+pub fn parse_sharpen(value: &str) -> Result<f32, String> {
+    let amount: f32 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid sharpen amount '{}', expected a number", value))?;
+
+    if !(amount > 0.0 && amount <= MAX_SHARPEN) {
+        return Err(format!(
+            "Sharpen amount must be greater than 0 and at most {}, got {}",
+            MAX_SHARPEN, value
+        ));
+    }
+
+    Ok(amount)
+}
 
 /// Validate that an image file is not corrupted and has reasonable dimensions
-pub fn validate_image(image_path: &Path) -> io::Result<()> {
+pub fn validate_image(image_path: &Path) -> io::Result<ImageInfo> {
     // Check if file exists and has reasonable size
     let metadata = fs::metadata(image_path)?;
     let file_size = metadata.len();
@@ -35,7 +162,7 @@ pub fn validate_image(image_path: &Path) -> io::Result<()> {
     }
 
     // Attempt to decode the image to check for corruption
-    match image::open(image_path) {
+    match open_image(image_path) {
         Ok(img) => {
             // Additional validation: check image dimensions
             let (width, height) = img.dimensions();
@@ -47,21 +174,26 @@ pub fn validate_image(image_path: &Path) -> io::Result<()> {
             }
 
             // Check for reasonable image dimensions (not too small, not absurdly large)
-            if width < 10 || height < 10 {
+            if width < MIN_IMAGE_DIMENSION || height < MIN_IMAGE_DIMENSION {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
                     "Image dimensions too small, likely corrupted",
                 ));
             }
 
-            if width > 10000 || height > 10000 {
+            if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
                     "Image dimensions unreasonably large",
                 ));
             }
 
-            Ok(())
+            Ok(ImageInfo {
+                width,
+                height,
+                file_size,
+                auto_cropped: false,
+            })
         }
         Err(e) => Err(io::Error::new(
             io::ErrorKind::InvalidData,
@@ -70,305 +202,2268 @@ pub fn validate_image(image_path: &Path) -> io::Result<()> {
     }
 }
 
-/// Process an image by resizing it and converting to JPEG format
-pub fn process_image(
-    source_path: &Path,
-    target_path: &Path,
-    width: u32,
-    height: u32,
-) -> io::Result<()> {
-    // Open and decode the source image (PNG)
-    let img = image::open(source_path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+/// Image format for processed card images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// JPEG (no transparency)
+    Jpeg,
+    /// PNG (supports transparency)
+    Png,
+}
 
-    // Convert to RGB
-    let img = img.into_rgb8();
+impl OutputFormat {
+    /// File extension used for this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+        }
+    }
 
-    // Resize the image directly to target dimensions using Lanczos3 filter
-    let resized =
-        image::imageops::resize(&img, width, height, image::imageops::FilterType::Lanczos3);
+    fn image_format(self) -> ImageFormat {
+        match self {
+            Self::Jpeg => ImageFormat::Jpeg,
+            Self::Png => ImageFormat::Png,
+        }
+    }
 
-    // Save the processed image as JPEG with high quality
-    resized
-        .save_with_format(target_path, image::ImageFormat::Jpeg)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    fn supports_alpha(self) -> bool {
+        matches!(self, Self::Png)
+    }
+}
 
-    // Final validation: ensure the processed JPEG is not corrupted
-    // This catches any corruption that might have occurred during processing
-    validate_image(target_path)?;
+/// Color channels kept in processed card images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Channels {
+    /// Drop any alpha channel
+    Rgb,
+    /// Keep transparency when the output format supports it
+    Rgba,
+}
 
-    // Delete the temporary PNG file
-    fs::remove_file(source_path)?;
+/// Histogram equalization applied to source images before resizing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AutoContrast {
+    /// Equalize brightness and scale each pixel's color by the same factor, keeping hues
+    Luminance,
+    /// Equalize red, green and blue independently, which also corrects color casts
+    PerChannel,
+}
 
-    Ok(())
+/// What `--require-https` does with image URLs that are not HTTPS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HttpsPolicy {
+    /// Skip the card without downloading
+    Reject,
+    /// Rewrite `http://` URLs to `https://`; other schemes are still rejected
+    Upgrade,
 }
 
-/// Download and process card images from JSON data
-pub async fn download_card_images(
-    json_path: &str,
-    output_dir: &str,
-    amount: Option<&str>,
-    thread_count: usize,
-    width: u32,
-    height: u32,
-    tcg_type: &TcgType,
-) -> io::Result<(usize, usize)> {
-    let client = reqwest::Client::new();
-    let images_dir = Path::new(output_dir).join("data/train");
-    fs::create_dir_all(&images_dir)?;
+/// Apply an HTTPS policy to an image URL, returning the URL to download or `None` to skip it
+///
+/// The boolean is true when the URL was upgraded.
+fn enforce_https(url: &str, policy: HttpsPolicy) -> Option<(String, bool)> {
+    let mut parsed = reqwest::Url::parse(url).ok()?;
+    match parsed.scheme() {
+        "https" => Some((url.to_string(), false)),
+        "http" if policy == HttpsPolicy::Upgrade => {
+            parsed.set_scheme("https").ok()?;
+            Some((parsed.to_string(), true))
+        }
+        _ => None,
+    }
+}
 
-    // Read and parse the JSON file
-    let json_content = fs::read_to_string(json_path)?;
+/// Settings for resizing and encoding downloaded images
+#[derive(Debug, Clone)]
+pub struct ImageProcessing {
+    pub width: u32,
+    pub height: u32,
+    pub format: OutputFormat,
+    pub channels: Channels,
+    /// Downscale sources whose longest side exceeds this before any other processing
+    pub max_dimension: Option<u32>,
+    /// Drop JFIF/EXIF/ICC and comment segments from JPEG output
+    pub strip_metadata: bool,
+    /// Equalize the source histogram before resizing
+    pub auto_contrast: Option<AutoContrast>,
+    /// Unsharp-mask radius (sigma) applied after resizing
+    pub sharpen: Option<f32>,
+    /// Crop sources to a detected card boundary before resizing
+    pub auto_crop: bool,
+    /// Write one image per size instead of one at `width`x`height`
+    pub sizes: Vec<(u32, u32)>,
+    /// Convert sources with an embedded ICC profile to sRGB before any other processing
+    pub convert_srgb: bool,
+}
 
-    // Try to determine format and create unified cards
-    let unified_cards: Vec<UnifiedCard> = if json_path.contains("ga_cards") {
-        // Parse GA format
-        let ga_cards: Vec<serde_json::Value> = serde_json::from_str(&json_content)?;
-        ga_cards
-            .into_iter()
-            .map(|card| UnifiedCard {
-                id: card["slug"].as_str().unwrap_or("unknown").to_string(),
-                image_url: card["image"].as_str().unwrap_or("").to_string(),
+impl ImageProcessing {
+    /// File name of the primary processed image in each card directory
+    pub fn primary_image_name(&self) -> String {
+        let (path, _, _) = self
+            .output_paths(Path::new(&format!("0000.{}", self.format.extension())))
+            .swap_remove(0);
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Files written for an image at `path`, with their dimensions
+    ///
+    /// With `sizes` each file is named by its size, e.g. `0000.jpg` becomes
+    /// `0000_224x224.jpg`, `0000_384x384.jpg`, ...
+    pub fn output_paths(&self, path: &Path) -> Vec<(PathBuf, u32, u32)> {
+        if self.sizes.is_empty() {
+            return vec![(path.to_path_buf(), self.width, self.height)];
+        }
+
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        self.sizes
+            .iter()
+            .map(|&(width, height)| {
+                let name = format!("{}_{}x{}.{}", stem, width, height, self.format.extension());
+                (path.with_file_name(name), width, height)
             })
             .collect()
-    } else {
-        // Parse MTG format - need to define temporary struct for deserialization
-        #[derive(serde::Deserialize)]
-        struct TempMtgCard {
-            id: String,
-            image_uris: Option<TempImageUris>,
+    }
+}
+
+/// Process an image by resizing it and encoding it in the configured format
+///
+/// The source is decoded once and resized to every output size, see
+/// [`ImageProcessing::output_paths`]. Returns what validation of the first
+/// written image found.
+pub fn process_image(
+    source_path: &Path,
+    target_path: &Path,
+    processing: &ImageProcessing,
+) -> io::Result<ImageInfo> {
+    // Open and decode the source image
+    let mut img = open_image(source_path)?;
+
+    if processing.convert_srgb {
+        img = convert_to_srgb(img, source_path)?;
+    }
+
+    // Bound memory and compute for huge scans before further processing
+    if let Some(max_dimension) = processing.max_dimension {
+        let (src_width, src_height) = img.dimensions();
+        if src_width.max(src_height) > max_dimension {
+            img = img.resize(
+                max_dimension,
+                max_dimension,
+                image::imageops::FilterType::Lanczos3,
+            );
         }
+    }
 
-        #[derive(serde::Deserialize)]
-        struct TempImageUris {
-            png: String,
+    // Crop before equalizing so the background does not skew the histogram
+    let mut auto_cropped = false;
+    if processing.auto_crop {
+        if let Some((x, y, width, height)) = detect_card_bounds(&img) {
+            img = img.crop_imm(x, y, width, height);
+            auto_cropped = true;
         }
+    }
 
-        let mtg_cards: Vec<TempMtgCard> = serde_json::from_str(&json_content)?;
-        mtg_cards
-            .into_iter()
-            .filter_map(|card| {
-                if let Some(image_uris) = card.image_uris {
-                    Some(UnifiedCard {
-                        id: card.id,
-                        image_url: image_uris.png,
-                    })
-                } else {
-                    None
-                }
-            })
-            .collect()
+    if let Some(mode) = processing.auto_contrast {
+        img = equalize_histogram(img, mode);
+    }
+
+    // Keep alpha only when requested and the output format can store it
+    let keep_alpha = processing.channels == Channels::Rgba && processing.format.supports_alpha();
+    let filter = image::imageops::FilterType::Lanczos3;
+
+    // Convert once so every size is resized from the same pixels
+    let img = if keep_alpha {
+        DynamicImage::ImageRgba8(img.into_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(img.into_rgb8())
     };
 
-    let total_available = unified_cards.len();
+    let mut first_info = None;
+    for (output_path, width, height) in processing.output_paths(target_path) {
+        // Resize the image directly to target dimensions using Lanczos3 filter
+        let resized = img.resize_exact(width, height, filter);
 
-    // Handle amount parameter
-    let mut cards_to_process = unified_cards;
-    if let Some(amt) = amount {
-        if amt != "all" {
-            if let Ok(limit) = amt.parse::<usize>() {
-                cards_to_process.truncate(limit);
-            } else {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "Invalid amount value",
-                ));
-            }
+        // Recover detail softened by downscaling
+        let resized = match processing.sharpen {
+            Some(sigma) => resized.unsharpen(sigma, SHARPEN_THRESHOLD),
+            None => resized,
+        };
+
+        // Save the processed image in the configured format
+        if processing.strip_metadata && processing.format == OutputFormat::Jpeg {
+            let mut encoded = Cursor::new(Vec::new());
+            resized
+                .write_to(&mut encoded, ImageFormat::Jpeg)
+                .map_err(io::Error::other)?;
+            fs::write(&output_path, strip_jpeg_metadata(encoded.get_ref()))?;
+        } else {
+            resized
+                .save_with_format(&output_path, processing.format.image_format())
+                .map_err(io::Error::other)?;
         }
+
+        // Final validation: ensure the processed image is not corrupted
+        // This catches any corruption that might have occurred during processing
+        let info = validate_image(&output_path)?;
+        first_info.get_or_insert(info);
     }
+    let info = first_info.expect("at least one output size");
 
-    let total_cards = cards_to_process.len();
-    println!(
-        "Found {} cards with images, downloading {} cards using {} threads",
-        total_available, total_cards, thread_count
-    );
+    // Delete the temporary downloaded file
+    fs::remove_file(source_path)?;
 
-    // Batch check which cards already exist
-    let card_ids: Vec<String> = cards_to_process
-        .iter()
-        .map(|card| card.id.clone())
+    Ok(ImageInfo {
+        auto_cropped,
+        ..info
+    })
+}
+
+/// Convert a decoded image to sRGB using the ICC profile embedded in its source file
+///
+/// Images without a profile are assumed to be sRGB already. Profiles that can't be
+/// applied to RGB pixels, such as CMYK or grayscale ones, leave the image unchanged.
+fn convert_to_srgb(img: DynamicImage, source_path: &Path) -> io::Result<DynamicImage> {
+    let icc = ImageReader::open(source_path)?
+        .with_guessed_format()?
+        .into_decoder()
+        .and_then(|mut decoder| decoder.icc_profile())
+        .map_err(io::Error::other)?;
+    let Some(input) = icc.and_then(|icc| qcms::Profile::new_from_slice(&icc, false)) else {
+        return Ok(img);
+    };
+    let mut output = qcms::Profile::new_sRGB();
+    output.precache_output_transform();
+
+    let has_alpha = img.color().has_alpha();
+    let data_type = if has_alpha {
+        qcms::DataType::RGBA8
+    } else {
+        qcms::DataType::RGB8
+    };
+    let Some(transform) =
+        qcms::Transform::new(&input, &output, data_type, qcms::Intent::Perceptual)
+    else {
+        return Ok(img);
+    };
+
+    Ok(if has_alpha {
+        let mut pixels = img.into_rgba8();
+        transform.apply(&mut pixels);
+        DynamicImage::ImageRgba8(pixels)
+    } else {
+        let mut pixels = img.into_rgb8();
+        transform.apply(&mut pixels);
+        DynamicImage::ImageRgb8(pixels)
+    })
+}
+
+/// Width-to-height ratio of a standard trading card (63 x 88 mm)
+const CARD_ASPECT_RATIO: f32 = 63.0 / 88.0;
+
+/// How far a detected boundary's aspect ratio may be from a card's, relative
+const AUTO_CROP_ASPECT_TOLERANCE: f32 = 0.08;
+
+/// Share of the image a detected card must cover, so specks and already
+/// tightly cropped images are both left alone
+const AUTO_CROP_AREA_RANGE: std::ops::RangeInclusive<f32> = 0.2..=0.95;
+
+/// Longest side of the downscaled copy the boundary is searched on
+const AUTO_CROP_ANALYSIS_SIZE: u32 = 400;
+
+/// Average difference of the outermost pixels from their mean color above which
+/// the background is not plain enough to separate from the card
+const AUTO_CROP_MAX_BACKGROUND_SPREAD: f32 = 12.0;
+
+/// Channel difference from the background color that marks a pixel as card
+const AUTO_CROP_FOREGROUND_DIFFERENCE: f32 = 40.0;
+
+/// Find the card in a photo or scan on a plain background, as (x, y, width, height)
+///
+/// The background color is taken from the image's outermost pixels, which must be
+/// nearly uniform, and the largest contour of pixels that differ from it is the
+/// card. Returns `None` unless that contour has a card's aspect ratio and trims a
+/// real margin, so images that are already cropped are never touched.
+fn detect_card_bounds(img: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = img.dimensions();
+    let scale = (AUTO_CROP_ANALYSIS_SIZE as f32 / width.max(height) as f32).min(1.0);
+    let small = img
+        .resize_exact(
+            ((width as f32 * scale).round() as u32).max(1),
+            ((height as f32 * scale).round() as u32).max(1),
+            image::imageops::FilterType::Triangle,
+        )
+        .into_rgba8();
+    let (small_width, small_height) = small.dimensions();
+    if small_width < 8 || small_height < 8 {
+        return None;
+    }
+
+    let difference = |a: &image::Rgba<u8>, b: &[f32; 4]| {
+        a.0.iter()
+            .zip(b)
+            .map(|(&channel, &mean)| (channel as f32 - mean).abs())
+            .fold(0.0, f32::max)
+    };
+
+    // Transparent corners or a busy background make the frame uneven, so bail out
+    let frame: Vec<&image::Rgba<u8>> = small
+        .enumerate_pixels()
+        .filter(|(x, y, _)| *x == 0 || *y == 0 || *x == small_width - 1 || *y == small_height - 1)
+        .map(|(_, _, pixel)| pixel)
         .collect();
-    let existing_cards = batch_check_existing_cards(output_dir, &card_ids);
+    let mut background = [0f32; 4];
+    for pixel in &frame {
+        for (sum, &channel) in background.iter_mut().zip(pixel.0.iter()) {
+            *sum += channel as f32;
+        }
+    }
+    background
+        .iter_mut()
+        .for_each(|sum| *sum /= frame.len() as f32);
+    let spread = frame
+        .iter()
+        .map(|pixel| difference(pixel, &background))
+        .sum::<f32>()
+        / frame.len() as f32;
+    if spread > AUTO_CROP_MAX_BACKGROUND_SPREAD {
+        return None;
+    }
 
-    // Filter out cards that already exist
-    let cards_to_download: Vec<_> = cards_to_process
+    let mask = image::GrayImage::from_fn(small_width, small_height, |x, y| {
+        let foreground =
+            difference(small.get_pixel(x, y), &background) > AUTO_CROP_FOREGROUND_DIFFERENCE;
+        image::Luma([if foreground { 255 } else { 0 }])
+    });
+    let (min_x, min_y, max_x, max_y) = imageproc::contours::find_contours::<u32>(&mask)
         .into_iter()
-        .filter(|card| !existing_cards.get(&card.id).unwrap_or(&false))
-        .collect();
+        .filter(|contour| contour.border_type == imageproc::contours::BorderType::Outer)
+        .filter_map(|contour| {
+            let xs = contour.points.iter().map(|point| point.x);
+            let ys = contour.points.iter().map(|point| point.y);
+            Some((xs.clone().min()?, ys.clone().min()?, xs.max()?, ys.max()?))
+        })
+        .max_by_key(|(min_x, min_y, max_x, max_y)| (max_x - min_x + 1) * (max_y - min_y + 1))?;
 
-    let cards_to_download_count = cards_to_download.len();
-    let already_existed = total_cards - cards_to_download_count;
+    let box_width = (max_x - min_x + 1) as f32;
+    let box_height = (max_y - min_y + 1) as f32;
+    let area = box_width * box_height / (small_width * small_height) as f32;
+    let aspect = box_width / box_height;
+    let card_like = [CARD_ASPECT_RATIO, 1.0 / CARD_ASPECT_RATIO]
+        .iter()
+        .any(|ratio| (aspect / ratio - 1.0).abs() <= AUTO_CROP_ASPECT_TOLERANCE);
+    if !AUTO_CROP_AREA_RANGE.contains(&area) || !card_like {
+        return None;
+    }
 
-    println!("Skipping {} cards that already exist", already_existed);
-    println!("Downloading {} new cards", cards_to_download_count);
+    // Map the box back to the full-size image
+    let x = ((min_x as f32 / scale) as u32).min(width - 1);
+    let y = ((min_y as f32 / scale) as u32).min(height - 1);
+    let crop_width = (((max_x + 1) as f32 / scale).round() as u32).min(width) - x;
+    let crop_height = (((max_y + 1) as f32 / scale).round() as u32).min(height) - y;
+    Some((x, y, crop_width, crop_height))
+}
 
-    if cards_to_download.is_empty() {
-        return Ok((already_existed, 0));
+/// Rough peak memory needed to validate and process a downloaded image
+///
+/// Counts the decoded source twice (decoding and the RGB(A) conversion for
+/// resizing) plus the resized output, at four bytes per pixel. Reading the
+/// dimensions only parses the header; when that fails the file size is scaled
+/// by a typical compression ratio instead.
+fn estimated_processing_bytes(path: &Path, processing: &ImageProcessing) -> u64 {
+    let target = processing.width as u64 * processing.height as u64 * 4;
+    match image::image_dimensions(path) {
+        Ok((width, height)) => width as u64 * height as u64 * 4 * 2 + target,
+        Err(_) => fs::metadata(path).map_or(0, |metadata| metadata.len() * 10) + target,
     }
+}
 
-    let pb = ProgressBar::new(cards_to_download_count as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-            )
-            .unwrap()
-            .progress_chars("#>-"),
-    );
+/// Map each 8-bit value so the cumulative histogram becomes linear
+fn equalization_lut(histogram: &[u64; 256]) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    let total: u64 = histogram.iter().sum();
+    let cdf_min = histogram
+        .iter()
+        .copied()
+        .find(|&count| count > 0)
+        .unwrap_or(0);
 
-    let pb_clone = pb.clone();
-    let skipped_existing = Arc::new(AtomicUsize::new(already_existed));
-    let skipped_soon = Arc::new(AtomicUsize::new(0));
+    // A single-valued (or empty) histogram has nothing to stretch
+    if total == cdf_min {
+        for (value, mapped) in lut.iter_mut().enumerate() {
+            *mapped = value as u8;
+        }
+        return lut;
+    }
 
-    let downloads = cards_to_download.into_iter().map(|card| {
-        let card_dir = images_dir.join(&card.id);
-        let (temp_ext, final_ext) = match tcg_type {
-            TcgType::Mtg => ("png", "jpg"),
-            TcgType::Ga => ("jpg", "jpg"),
-        };
-        let temp_file_path = card_dir.join(format!("temp.{}", temp_ext));
-        let final_file_path = card_dir.join(format!("0000.{}", final_ext));
-        let client = client.clone();
-        let pb = pb_clone.clone();
-        let skipped_soon_clone = skipped_soon.clone();
-        let image_url = card.image_url.clone();
-
-        {
-            let temp_path = temp_file_path.clone();
-            let final_path = final_file_path.clone();
-            async move {
-                // Create card directory
-                if let Err(e) = fs::create_dir_all(final_path.parent().unwrap()) {
-                    pb.inc(1);
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("Failed to create card directory: {}", e),
-                    ));
-                }
+    let mut cdf = 0;
+    for (value, count) in histogram.iter().enumerate() {
+        cdf += count;
+        lut[value] =
+            ((cdf.saturating_sub(cdf_min)) as f64 / (total - cdf_min) as f64 * 255.0).round() as u8;
+    }
+    lut
+}
 
-                // Skip cards with placeholder "soon.jpg" image (MTG specific)
-                if image_url.contains("errors.scryfall.com/soon.jpg") {
-                    skipped_soon_clone.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    pb.inc(1);
-                    return Ok(());
+/// Equalize an image's histogram, ignoring fully transparent pixels
+fn equalize_histogram(img: DynamicImage, mode: AutoContrast) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let mut rgba = img.into_rgba8();
+    let visible = |pixel: &image::Rgba<u8>| pixel[3] > 0;
+    let luma = |pixel: &image::Rgba<u8>| {
+        (0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32).round() as u8
+    };
+
+    match mode {
+        AutoContrast::PerChannel => {
+            for channel in 0..3 {
+                let mut histogram = [0u64; 256];
+                for pixel in rgba.pixels().filter(|pixel| visible(pixel)) {
+                    histogram[pixel[channel] as usize] += 1;
+                }
+                let lut = equalization_lut(&histogram);
+                for pixel in rgba.pixels_mut() {
+                    pixel[channel] = lut[pixel[channel] as usize];
+                }
+            }
+        }
+        AutoContrast::Luminance => {
+            let mut histogram = [0u64; 256];
+            for pixel in rgba.pixels().filter(|pixel| visible(pixel)) {
+                histogram[luma(pixel) as usize] += 1;
+            }
+            let lut = equalization_lut(&histogram);
+            for pixel in rgba.pixels_mut() {
+                let y = luma(pixel);
+                if y == 0 {
+                    continue;
+                }
+                let scale = lut[y as usize] as f32 / y as f32;
+                for channel in 0..3 {
+                    pixel[channel] = (pixel[channel] as f32 * scale).clamp(0.0, 255.0) as u8;
                 }
+            }
+        }
+    }
 
-                match client
-                    .get(&image_url)
-                    .header("User-Agent", get_user_agent())
-                    .send()
-                    .await
-                {
-                    Ok(response) => {
-                        if !response.status().is_success() {
-                            pb.inc(1);
-                            return Err(io::Error::new(
-                                io::ErrorKind::Other,
-                                format!("HTTP {} for URL: {}", response.status(), image_url),
-                            ));
-                        }
+    if has_alpha {
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).into_rgb8())
+    }
+}
 
-                        match response.bytes().await {
-                            Ok(bytes) => {
-                                let mut file = fs::File::create(&temp_path)?;
-                                file.write_all(&bytes)?;
-
-                                if let Err(e) = validate_image(&temp_path) {
-                                    if let Err(cleanup_err) = fs::remove_file(&temp_path) {
-                                        eprintln!(
-                                            "Failed to cleanup corrupted image file: {}",
-                                            cleanup_err
-                                        );
-                                    }
-                                    pb.inc(1);
-                                    return Err(io::Error::new(
-                                        io::ErrorKind::InvalidData,
-                                        format!(
-                                            "Corrupted image detected: {} - URL: {}",
-                                            e, image_url
-                                        ),
-                                    ));
-                                }
-                                if let Err(e) =
-                                    process_image(&temp_path, &final_path, width, height)
-                                {
-                                    // Only try to cleanup temp file if it still exists (process_image failed)
-                                    if temp_path.exists() {
-                                        if let Err(cleanup_err) = fs::remove_file(&temp_path) {
-                                            eprintln!(
-                                                "Failed to cleanup temp file: {}",
-                                                cleanup_err
-                                            );
-                                        }
-                                    }
-                                    pb.inc(1);
-                                    return Err(e);
-                                }
-
-                                pb.inc(1);
-                                Ok(())
-                            }
-                            Err(e) => {
-                                pb.inc(1);
-                                Err(io::Error::new(
-                                    io::ErrorKind::Other,
-                                    format!("Failed to read response bytes: {}", e),
-                                ))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        pb.inc(1);
-                        Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("HTTP request failed: {}", e),
-                        ))
-                    }
-                }
+/// Remove APPn (JFIF, EXIF, ICC, ...) and comment segments from an encoded JPEG
+///
+/// Segments are only walked up to the start of scan; anything unexpected leaves
+/// the remaining bytes untouched so the image always stays decodable.
+fn strip_jpeg_metadata(jpeg: &[u8]) -> Vec<u8> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return jpeg.to_vec();
+    }
+
+    let mut stripped = Vec::with_capacity(jpeg.len());
+    stripped.extend_from_slice(&jpeg[..2]);
+    let mut pos = 2;
+
+    while pos + 4 <= jpeg.len() && jpeg[pos] == 0xFF {
+        let marker = jpeg[pos + 1];
+        // Start of scan: the entropy-coded data follows, copy the rest verbatim
+        if marker == 0xDA {
+            break;
+        }
+        let length = u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+        let end = pos + 2 + length;
+        if length < 2 || end > jpeg.len() {
+            break;
+        }
+        if !matches!(marker, 0xE0..=0xEF | 0xFE) {
+            stripped.extend_from_slice(&jpeg[pos..end]);
+        }
+        pos = end;
+    }
+
+    stripped.extend_from_slice(&jpeg[pos..]);
+    stripped
+}
+
+/// Order in which cards are downloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CardSort {
+    /// Sort by card name
+    Name,
+    /// Sort by set code
+    Set,
+    /// Sort by card id
+    Id,
+    /// Sort by rarity, from most common to rarest
+    Rarity,
+}
+
+/// How card directories under `data/train` are named
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DirNaming {
+    /// The card id (Scryfall id or GA slug)
+    #[default]
+    Id,
+    /// `<set>-<collector_number>` for MTG cards, falling back to the id
+    Collector,
+}
+
+/// Name each card's directory according to `naming`
+///
+/// Collector names that several cards share, such as printings of one card in
+/// different languages, get the language appended. Cards without a collector
+/// number, or whose name still collides, keep their id.
+///
+/// Every name is then made safe for the filesystem, and names that are already
+/// taken (ignoring case, for case-insensitive filesystems) get `-2`, `-3`, ...
+/// appended in file order. Returns the ids of the cards renamed that way.
+pub fn apply_dir_naming(cards: &mut [UnifiedCard], naming: DirNaming) -> Vec<String> {
+    if naming == DirNaming::Collector {
+        apply_collector_naming(cards);
+    }
+
+    let mut seen = HashSet::new();
+    let mut collisions = Vec::new();
+    for card in cards.iter_mut() {
+        let name = card.dir_name.as_deref().unwrap_or(&card.id);
+        let safe = sanitize_component(Some(name));
+        let mut unique = safe.clone();
+        let mut suffix = 2;
+        while !seen.insert(unique.to_lowercase()) {
+            unique = format!("{}-{}", safe, suffix);
+            suffix += 1;
+        }
+        if unique != safe {
+            collisions.push(card.id.clone());
+        }
+        if unique != name {
+            card.dir_name = Some(unique);
+        }
+    }
+    collisions
+}
+
+/// Name directories `<set>-<collector_number>` where that is unambiguous
+fn apply_collector_naming(cards: &mut [UnifiedCard]) {
+    let collector_name = |card: &UnifiedCard| {
+        let set = card.set.as_deref()?;
+        let number = card.collector_number.as_deref()?;
+        Some(format!("{}-{}", set, number).replace(['/', '\\'], "_"))
+    };
+    let is_back = |card: &UnifiedCard| card.id.ends_with("-back");
+
+    let mut shared: HashMap<(String, bool), usize> = HashMap::new();
+    for card in cards.iter() {
+        if let Some(name) = collector_name(card) {
+            *shared.entry((name, is_back(card))).or_default() += 1;
+        }
+    }
+
+    let mut taken: HashMap<String, usize> = HashMap::new();
+    for card in cards.iter_mut() {
+        let Some(mut name) = collector_name(card) else {
+            continue;
+        };
+        let back = is_back(card);
+        if shared[&(name.clone(), back)] > 1 {
+            if let Some(lang) = &card.lang {
+                name = format!("{}-{}", name, lang);
             }
         }
+        if back {
+            name.push_str("-back");
+        }
+        *taken.entry(name.clone()).or_default() += 1;
+        card.dir_name = Some(name);
+    }
+
+    for card in cards.iter_mut() {
+        if card.dir_name.as_ref().is_some_and(|name| taken[name] > 1) {
+            card.dir_name = None;
+        }
+    }
+}
+
+/// Drop every card whose set already has a directory under `data/train`
+///
+/// Sets are treated as complete once their directory holds any card, which saves
+/// checking each card's image individually.
+fn skip_downloaded_sets(cards: &mut Vec<UnifiedCard>, output_dir: &str) -> io::Result<()> {
+    let existing = crate::utils::files::list_set_directories(output_dir)?;
+    if existing.is_empty() {
+        return Ok(());
+    }
+
+    let mut skipped = HashSet::new();
+    cards.retain(|card| match &card.set {
+        Some(set) if existing.contains(set) => {
+            skipped.insert(set.clone());
+            false
+        }
+        _ => true,
     });
 
-    let semaphore = Arc::new(tokio::sync::Semaphore::new(thread_count));
-    let results: Vec<_> = futures::stream::iter(downloads)
-        .map(|download| {
-            let semaphore = semaphore.clone();
-            async move {
-                let _permit = semaphore.acquire().await.unwrap();
-                download.await
-            }
-        })
-        .buffer_unordered(thread_count)
-        .collect()
-        .await;
+    if !skipped.is_empty() {
+        let mut skipped: Vec<String> = skipped.into_iter().collect();
+        skipped.sort();
+        println!(
+            "Skipping {} sets already downloaded: {}",
+            skipped.len(),
+            skipped.join(", ")
+        );
+    }
+    Ok(())
+}
 
-    pb.finish_with_message("Download complete!");
+/// Keep the first `per_set` cards of every set and report how many each set got
+fn sample_per_set(cards: &mut Vec<UnifiedCard>, per_set: usize) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    cards.retain(|card| {
+        let count = seen
+            .entry(card.set.clone().unwrap_or_default())
+            .or_default();
+        *count += 1;
+        *count <= per_set
+    });
 
-    let failed_downloads = results
-        .iter()
-        .filter(|r: &&Result<(), std::io::Error>| r.is_err())
-        .count();
-    if failed_downloads > 0 {
-        eprintln!("Warning: {} downloads failed", failed_downloads);
+    println!(
+        "Sampled {} cards from {} sets (up to {} per set)",
+        cards.len(),
+        seen.len(),
+        per_set
+    );
+    let mut short_sets: Vec<(&String, &usize)> =
+        seen.iter().filter(|(_, count)| **count < per_set).collect();
+    if !short_sets.is_empty() {
+        short_sets.sort();
+        println!(
+            "{} sets have fewer than {} cards:",
+            short_sets.len(),
+            per_set
+        );
+        for (set, count) in short_sets {
+            println!("  {}: {}", set, count);
+        }
+    }
+}
+
+/// Rank a rarity so that common cards sort before rare ones
+fn rarity_rank(rarity: &str) -> u32 {
+    match rarity {
+        "common" => 0,
+        "uncommon" => 1,
+        "rare" => 2,
+        "mythic" => 3,
+        "special" => 4,
+        "bonus" => 5,
+        // GA rarities are already numeric
+        other => other.parse().unwrap_or(u32::MAX),
+    }
+}
+
+/// Sort cards by the given key, keeping cards without the field at the end
+fn sort_cards(cards: &mut [UnifiedCard], sort: CardSort) {
+    match sort {
+        CardSort::Name => cards.sort_by(|a, b| compare_missing_last(&a.name, &b.name)),
+        CardSort::Set => cards.sort_by(|a, b| compare_missing_last(&a.set, &b.set)),
+        CardSort::Id => cards.sort_by(|a, b| a.id.cmp(&b.id)),
+        CardSort::Rarity => {
+            cards.sort_by_key(|card| card.rarity.as_deref().map_or(u32::MAX, rarity_rank))
+        }
     }
+}
 
-    let final_skipped_existing = skipped_existing.load(std::sync::atomic::Ordering::Relaxed);
-    let final_skipped_soon = skipped_soon.load(std::sync::atomic::Ordering::Relaxed);
+/// Compare optional fields, ordering missing values after present ones
+fn compare_missing_last(a: &Option<String>, b: &Option<String>) -> std::cmp::Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    }
+}
 
-    Ok((final_skipped_existing, final_skipped_soon))
+/// Options controlling how card images are downloaded and processed
+#[derive(Clone)]
+pub struct DownloadOptions {
+    pub client: reqwest::Client,
+    pub amount: Option<String>,
+    /// Downloads left in the run-wide budget shared by every file
+    pub remaining_total: Option<Arc<AtomicUsize>>,
+    pub thread_count: usize,
+    pub processing: ImageProcessing,
+    pub tcg_type: TcgType,
+    pub group_by_set: bool,
+    pub sort: Option<CardSort>,
+    /// Order cards by id before any `sort`, and report failures ordered by id
+    pub stable_order: bool,
+    /// Keep at most this many cards from each set, applied before `amount`
+    pub sample_per_set: Option<usize>,
+    pub filter: CardFilter,
+    pub adaptive_concurrency: bool,
+    /// Warn when no download completes for this long
+    pub stall_timeout: Option<Duration>,
+    /// Fallback host tried when a download from the primary image URL fails
+    pub image_mirror: Option<reqwest::Url>,
+    /// Directory for downloaded files awaiting validation, instead of the card directory
+    pub temp_dir: Option<PathBuf>,
+    pub dir_naming: DirNaming,
+    /// Reuse the parsed card list saved next to the source JSON
+    pub parse_cache: bool,
+    /// Skip or upgrade image URLs that are not HTTPS
+    pub require_https: Option<HttpsPolicy>,
+    /// Skip sets whose directory already exists (requires `group_by_set`)
+    pub only_new_sets: bool,
+    /// Estimated bytes of images decoded and processed at once, across all downloads
+    pub max_memory: Option<u64>,
+    /// Concurrent downloads allowed to any one host, within `thread_count`
+    pub per_host: Option<usize>,
+    /// Slow down requests to hosts that answer 429 until they recover
+    pub limit_rate_per_host: bool,
+    /// Times of day downloads run, pause or slow down
+    pub schedule: Option<Schedule>,
+    /// Where processed images are stored, `data/train` under the output path when unset
+    pub image_store: Option<Arc<dyn ImageStore>>,
+    pub only_ids: Option<Arc<HashSet<String>>>,
+    /// Cards requested with --card-list; others are skipped
+    pub card_list: Option<Arc<CardList>>,
+    pub manifest: Option<Arc<ManifestWriter>>,
+    /// Finished cards saved periodically, and skipped when left by an interrupted run
+    pub checkpoint: Option<Arc<Checkpoint>>,
+    /// Reports the downloads when the process receives SIGUSR1
+    pub progress: Option<Arc<ProgressReporter>>,
+    /// Download the new cards this many at a time instead of all in one pipeline
+    pub batch_size: Option<usize>,
+    pub cache: Option<ImageCache>,
+    /// Dataset whose processed images are hard-linked instead of downloaded
+    pub reference_dir: Option<PathBuf>,
+    pub output_template: Option<OutputTemplate>,
+    /// Log dimensions and size of the first this many processed images
+    pub preview: usize,
+    /// Record how long each card took to download and process
+    pub track_timings: bool,
 }
 
-/// Batch check which cards already exist to avoid re-downloading
-pub fn batch_check_existing_cards(base_path: &str, card_ids: &[String]) -> HashMap<String, bool> {
-    let train_dir = Path::new(base_path).join("data/train");
+impl DownloadOptions {
+    /// Path of a card's image relative to `data/train`, before any `--sizes` suffix
+    pub fn card_image_path(&self, card: &UnifiedCard) -> PathBuf {
+        match &self.output_template {
+            Some(template) => template.render(card, self.processing.format.extension()),
+            None => card
+                .relative_dir(self.group_by_set)
+                .join(format!("0000.{}", self.processing.format.extension())),
+        }
+    }
 
-    card_ids
-        .par_iter()
-        .map(|card_id| {
-            let card_dir = train_dir.join(card_id);
-            let final_jpg = card_dir.join("0000.jpg");
-            (card_id.clone(), final_jpg.exists())
-        })
-        .collect()
+    /// Paths of every file written for a card relative to `data/train`, primary image first
+    pub fn card_image_paths(&self, card: &UnifiedCard) -> Vec<PathBuf> {
+        self.processing
+            .output_paths(&self.card_image_path(card))
+            .into_iter()
+            .map(|(path, _, _)| path)
+            .collect()
+    }
 }
 
-// TODO: Add tests with proper test dependencies
+/// Which card objects are kept when loading a bulk JSON file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardFilter {
+    /// Include double-faced backs and the generic card back
+    pub include_backs: bool,
+    /// Keep MTG tokens, emblems and art cards
+    pub include_tokens: bool,
+    /// Drop digital-only (Arena/MTGO) MTG printings
+    pub exclude_digital: bool,
+    /// Drop promotional MTG printings (prerelease, buy-a-box, ...)
+    pub exclude_promos: bool,
+    /// Drop MTG cards whose Scryfall `image_status` is below this
+    pub min_image_status: ImageStatus,
+    /// Keep one MTG printing per oracle id
+    pub dedupe_by_oracle_id: bool,
+    /// Keep only MTG cards printed in this finish
+    pub finish: Option<Finish>,
+}
+
+/// Cards loaded from a bulk JSON file along with what the filter dropped
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LoadedCards {
+    pub cards: Vec<UnifiedCard>,
+    pub excluded_tokens: usize,
+    pub excluded_digital: usize,
+    pub excluded_promos: usize,
+    /// Cards dropped for a missing, placeholder or otherwise too low quality image
+    pub excluded_image_status: usize,
+    pub excluded_finish: Option<(Finish, usize)>,
+    /// MTG printings dropped because another printing of the card was kept
+    pub collapsed_printings: usize,
+    /// Entries that could not be parsed as cards, with the first parse error
+    pub unparseable: Option<(usize, String)>,
+    /// Ids of cards that have no usable image at all
+    pub imageless: Vec<String>,
+    /// Double-faced MTG cards skipped because backs were not requested
+    pub faces_only: usize,
+}
+
+impl LoadedCards {
+    /// Print how many objects were excluded by the card filter
+    pub fn report_exclusions(&self) {
+        if let Some((count, first_error)) = &self.unparseable {
+            println!(
+                "Skipped {} card entries that could not be parsed (first error: {})",
+                count, first_error
+            );
+        }
+        if self.excluded_tokens > 0 {
+            println!(
+                "Excluded {} tokens, emblems and art cards (use --include-tokens to keep them)",
+                self.excluded_tokens
+            );
+        }
+        if self.excluded_digital > 0 {
+            println!("Excluded {} digital-only printings", self.excluded_digital);
+        }
+        if self.excluded_image_status > 0 {
+            println!(
+                "Skipped {} cards whose Scryfall image is missing, a placeholder or below --min-image-status",
+                self.excluded_image_status
+            );
+        }
+        if self.excluded_promos > 0 {
+            println!("Excluded {} promotional printings", self.excluded_promos);
+        }
+        if let Some((finish, count)) = self.excluded_finish {
+            if count > 0 {
+                println!(
+                    "Excluded {} cards not printed in {} finish",
+                    count,
+                    finish.as_str()
+                );
+            }
+        }
+        if self.collapsed_printings > 0 {
+            println!(
+                "Collapsed {} printings sharing an oracle id, keeping one per card",
+                self.collapsed_printings
+            );
+        }
+        if self.faces_only > 0 {
+            println!(
+                "Skipped {} double-faced cards with per-face images only (use --include-backs to download them)",
+                self.faces_only
+            );
+        }
+        if !self.imageless.is_empty() {
+            println!(
+                "Skipped {} cards without a usable image (use --dry-validate to list them)",
+                self.imageless.len()
+            );
+        }
+    }
+}
+
+/// Card JSON smaller than this loads too quickly to be worth a progress bar
+const LOAD_PROGRESS_MIN_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Read a card JSON file and normalize it into unified cards with images
+///
+/// With `include_backs`, double-faced MTG cards yield their front face under the
+/// card id and their back face under `<id>-back`. Large files, like the MTG bulk
+/// data, show a byte progress bar while read and a spinner while parsed.
+pub fn load_unified_cards(
+    json_path: &str,
+    tcg_type: &TcgType,
+    filter: &CardFilter,
+) -> io::Result<LoadedCards> {
+    let size = fs::metadata(json_path)?.len();
+    if size < LOAD_PROGRESS_MIN_BYTES {
+        let json_content = fs::read_to_string(json_path)?;
+        return tcg_type.source().parse_cards(&json_content, filter);
+    }
+
+    let pb = ProgressBar::new(size);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.set_message("reading card JSON");
+    let mut json_content = String::with_capacity(size as usize);
+    pb.wrap_read(fs::File::open(json_path)?)
+        .read_to_string(&mut json_content)?;
+
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} [{elapsed_precise}] {msg}")
+            .unwrap(),
+    );
+    pb.set_message(format!("parsing {} of card JSON", HumanBytes(size)));
+    pb.enable_steady_tick(Duration::from_millis(100));
+    let loaded = tcg_type.source().parse_cards(&json_content, filter);
+    pb.finish_and_clear();
+    loaded
+}
+
+/// Outcome of downloading the images for one JSON file
+#[derive(Debug, Default)]
+pub struct DownloadSummary {
+    pub downloaded: usize,
+    pub skipped_existing: usize,
+    pub skipped_soon: usize,
+    pub failed: Vec<FailedDownload>,
+    /// How many of the failures ran into the redirect limit
+    pub redirect_failures: usize,
+    /// Images served by the fallback mirror
+    pub mirrored: usize,
+    /// Cards hard-linked from the reference dataset instead of downloaded
+    pub linked: usize,
+    /// Non-HTTPS image URLs skipped by `--require-https`
+    pub insecure_rejected: usize,
+    /// Plain HTTP image URLs rewritten to HTTPS by `--require-https upgrade`
+    pub https_upgraded: usize,
+    /// Images cropped to a detected card boundary by `--auto-crop`
+    pub auto_cropped: usize,
+    /// Time taken by each card attempted, keyed by id, with `track_timings`
+    pub timings: HashMap<String, CardTiming>,
+}
+
+/// How long one card took to download and process, whether or not it succeeded
+#[derive(Debug, Clone)]
+pub struct CardTiming {
+    pub image_url: String,
+    pub elapsed: Duration,
+}
+
+/// Outcome of re-validating the images produced for one JSON file
+#[derive(Debug, Default)]
+pub struct VerifySummary {
+    pub checked: usize,
+    pub corrupted: Vec<String>,
+}
+
+/// Re-validate every downloaded image for the cards in a JSON file
+///
+/// Corrupted images are deleted so the next download run fetches them again;
+/// images that were never downloaded are not counted.
+pub fn verify_card_images(
+    json_path: &str,
+    output_dir: &str,
+    options: &DownloadOptions,
+) -> io::Result<VerifySummary> {
+    let images_dir = Path::new(output_dir).join("data/train");
+    let mut cards = load_cards(
+        json_path,
+        &options.tcg_type,
+        &options.filter,
+        options.parse_cache,
+    )?
+    .cards;
+    apply_dir_naming(&mut cards, options.dir_naming);
+    if options.filter.include_backs {
+        cards.extend(options.tcg_type.source().card_back());
+    }
+
+    let results: Vec<(String, io::Result<ImageInfo>)> = cards
+        .par_iter()
+        .flat_map_iter(|card| {
+            options
+                .card_image_paths(card)
+                .into_iter()
+                .map(|path| images_dir.join(path))
+                .filter(|image_path| image_path.exists())
+                .map(|image_path| {
+                    let result = validate_image(&image_path);
+                    if let Err(e) = &result {
+                        eprintln!("Corrupted image {}: {}", image_path.display(), e);
+                        if let Err(e) = fs::remove_file(&image_path) {
+                            eprintln!("Failed to remove {}: {}", image_path.display(), e);
+                        }
+                    }
+                    (card.id.clone(), result)
+                })
+        })
+        .collect();
+
+    Ok(VerifySummary {
+        checked: results.len(),
+        corrupted: results
+            .into_iter()
+            .filter(|(_, result)| result.is_err())
+            .map(|(id, _)| id)
+            .collect(),
+    })
+}
+
+/// Outcome of reprocessing the images already in a dataset
+#[derive(Debug, Default)]
+pub struct ConvertSummary {
+    pub converted: usize,
+    pub failed: usize,
+}
+
+/// Reprocess every card's primary image with new processing settings, offline
+///
+/// Each card's existing `0000.jpg`/`0000.png` is the source, so converting from
+/// JPEG compounds its compression loss. Augmented images are left alone; run
+/// `augment` again to regenerate them.
+pub fn convert_existing_images(
+    base_path: &str,
+    group_by_set: bool,
+    processing: &ImageProcessing,
+) -> io::Result<ConvertSummary> {
+    let train_dir = Path::new(base_path).join("data/train");
+    let card_dirs = crate::utils::files::list_card_directories(base_path, group_by_set)?;
+
+    let pb = ProgressBar::new(card_dirs.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+
+    let results: Vec<io::Result<bool>> = card_dirs
+        .par_iter()
+        .map(|relative| {
+            let result = convert_card_image(&train_dir.join(relative), processing);
+            if let Err(e) = &result {
+                pb.println(format!("Failed to convert {}: {}", relative.display(), e));
+            }
+            pb.inc(1);
+            result
+        })
+        .collect();
+    pb.finish_and_clear();
+
+    Ok(ConvertSummary {
+        converted: results
+            .iter()
+            .filter(|result| matches!(result, Ok(true)))
+            .count(),
+        failed: results.iter().filter(|result| result.is_err()).count(),
+    })
+}
+
+/// Convert one card directory's primary image, returning false when it has none
+fn convert_card_image(card_dir: &Path, processing: &ImageProcessing) -> io::Result<bool> {
+    let Some(source) = find_primary_image(card_dir) else {
+        return Ok(false);
+    };
+
+    // process_image deletes its source, which may also be the target, so work
+    // from a temp file that `clean` recognizes if the run is interrupted
+    let extension = source.extension().unwrap_or_default().to_string_lossy();
+    let temp_path = card_dir.join(format!("temp.{}", extension));
+    fs::rename(&source, &temp_path)?;
+
+    let target = card_dir.join(processing.primary_image_name());
+    match process_image(&temp_path, &target, processing) {
+        Ok(_) => Ok(true),
+        Err(e) => {
+            // Put the original back so a failed conversion loses nothing
+            if target != source && target.exists() {
+                fs::remove_file(&target)?;
+            }
+            fs::rename(&temp_path, &source)?;
+            Err(e)
+        }
+    }
+}
+
+/// Hard-link a card's processed images from a reference dataset's `data/train`
+///
+/// Returns false, linking nothing, unless every file the card needs is there at the
+/// size the current options produce. Files are copied when the reference is on
+/// another filesystem.
+fn link_from_reference(
+    reference_train: &Path,
+    images_dir: &Path,
+    relative_path: &Path,
+    processing: &ImageProcessing,
+) -> io::Result<bool> {
+    let outputs = processing.output_paths(relative_path);
+    for (path, width, height) in &outputs {
+        match validate_image(&reference_train.join(path)) {
+            Ok(info) if info.width == *width && info.height == *height => {}
+            _ => return Ok(false),
+        }
+    }
+
+    for (path, _, _) in &outputs {
+        let source = reference_train.join(path);
+        let target = images_dir.join(path);
+        if target.exists() {
+            fs::remove_file(&target)?;
+        }
+        if fs::hard_link(&source, &target).is_err() {
+            fs::copy(&source, &target)?;
+        }
+    }
+    Ok(true)
+}
+
+/// Locate a card directory's `0000.*` image, whatever format it was saved in
+fn find_primary_image(card_dir: &Path) -> Option<PathBuf> {
+    ["jpg", "jpeg", "png"]
+        .iter()
+        .map(|extension| card_dir.join(format!("0000.{}", extension)))
+        .find(|path| path.is_file())
+}
+
+/// Per-channel mean and standard deviation of a dataset's primary images
+///
+/// Values are RGB on a 0-1 scale, the form `torchvision.transforms.Normalize`
+/// and similar transforms take.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStats {
+    pub images: usize,
+    pub failed: usize,
+    pub mean: [f64; 3],
+    pub std: [f64; 3],
+}
+
+/// Running per-channel sums over pixels, merged across threads
+#[derive(Debug, Default, Clone, Copy)]
+struct ChannelSums {
+    images: usize,
+    failed: usize,
+    pixels: u64,
+    sum: [u64; 3],
+    sum_squares: [u64; 3],
+}
+
+impl ChannelSums {
+    fn merge(mut self, other: Self) -> Self {
+        self.images += other.images;
+        self.failed += other.failed;
+        self.pixels += other.pixels;
+        for channel in 0..3 {
+            self.sum[channel] += other.sum[channel];
+            self.sum_squares[channel] += other.sum_squares[channel];
+        }
+        self
+    }
+}
+
+/// Compute the channel mean and standard deviation over every card's primary image
+///
+/// Every pixel counts equally, so larger images weigh more. Augmented images are
+/// ignored so the statistics describe the source data.
+pub fn compute_channel_stats(base_path: &str, group_by_set: bool) -> io::Result<ChannelStats> {
+    let train_dir = Path::new(base_path).join("data/train");
+    let card_dirs = crate::utils::files::list_card_directories(base_path, group_by_set)?;
+
+    let pb = ProgressBar::new(card_dirs.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+
+    let sums = card_dirs
+        .par_iter()
+        .map(|relative| {
+            let mut sums = ChannelSums::default();
+            if let Some(path) = find_primary_image(&train_dir.join(relative)) {
+                match image::open(&path) {
+                    Ok(image) => {
+                        sums.images = 1;
+                        for pixel in image.to_rgb8().pixels() {
+                            sums.pixels += 1;
+                            for channel in 0..3 {
+                                let value = u64::from(pixel[channel]);
+                                sums.sum[channel] += value;
+                                sums.sum_squares[channel] += value * value;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        pb.println(format!("Failed to read {}: {}", path.display(), e));
+                        sums.failed = 1;
+                    }
+                }
+            }
+            pb.inc(1);
+            sums
+        })
+        .reduce(ChannelSums::default, ChannelSums::merge);
+    pb.finish_and_clear();
+
+    if sums.pixels == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No card images found under {}", train_dir.display()),
+        ));
+    }
+
+    let pixels = sums.pixels as f64;
+    let mut mean = [0.0; 3];
+    let mut std = [0.0; 3];
+    for channel in 0..3 {
+        let channel_mean = sums.sum[channel] as f64 / pixels / 255.0;
+        let mean_square = sums.sum_squares[channel] as f64 / pixels / (255.0 * 255.0);
+        mean[channel] = channel_mean;
+        std[channel] = (mean_square - channel_mean * channel_mean).max(0.0).sqrt();
+    }
+
+    Ok(ChannelStats {
+        images: sums.images,
+        failed: sums.failed,
+        mean,
+        std,
+    })
+}
+
+/// Error for a download that ran into the `--max-redirects` limit
+#[derive(Debug)]
+struct TooManyRedirects(String);
+
+impl fmt::Display for TooManyRedirects {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Too many redirects for URL: {}", self.0)
+    }
+}
+
+impl std::error::Error for TooManyRedirects {}
+
+/// Whether a download failed because it exceeded the redirect limit
+fn is_redirect_failure(error: &io::Error) -> bool {
+    error
+        .get_ref()
+        .is_some_and(|inner| inner.is::<TooManyRedirects>())
+}
+
+/// Point an image URL at a mirror, keeping its path and query
+///
+/// The mirror may include a path prefix, e.g. `https://mirror.example.com/scryfall`.
+fn mirror_url(image_url: &str, mirror: &reqwest::Url) -> Option<String> {
+    let original = reqwest::Url::parse(image_url).ok()?;
+    let mut mirrored = format!(
+        "{}{}",
+        mirror.as_str().trim_end_matches('/'),
+        original.path()
+    );
+    if let Some(query) = original.query() {
+        mirrored.push('?');
+        mirrored.push_str(query);
+    }
+    Some(mirrored)
+}
+
+/// Download an image, failing on non-success HTTP statuses
+async fn fetch_image_bytes(
+    client: &reqwest::Client,
+    image_url: &str,
+    throttle: Option<&HostThrottle>,
+) -> io::Result<Bytes> {
+    let response = get_with_rate_limit_retry(client, image_url, throttle)
+        .await
+        .map_err(|e| {
+            if e.is_redirect() {
+                io::Error::other(TooManyRedirects(image_url.to_string()))
+            } else {
+                io::Error::other(format!("HTTP request failed: {}", e))
+            }
+        })?;
+
+    // With redirects disabled (--max-redirects 0) the redirect itself comes back
+    if response.status().is_redirection() {
+        return Err(io::Error::other(TooManyRedirects(image_url.to_string())));
+    }
+    if !response.status().is_success() {
+        return Err(io::Error::other(format!(
+            "HTTP {} for URL: {}",
+            response.status(),
+            image_url
+        )));
+    }
+
+    response
+        .bytes()
+        .await
+        .map_err(|e| io::Error::other(format!("Failed to read response bytes: {}", e)))
+}
+
+/// Download and process card images from JSON data
+pub async fn download_card_images(
+    json_path: &str,
+    output_dir: &str,
+    options: &DownloadOptions,
+) -> io::Result<DownloadSummary> {
+    let thread_count = resolve_thread_count(options.thread_count);
+    let source = options.tcg_type.source();
+    let client = options.client.clone();
+    let images_dir = Path::new(output_dir).join("data/train");
+    fs::create_dir_all(&images_dir)?;
+    if let Some(temp_dir) = &options.temp_dir {
+        fs::create_dir_all(temp_dir)?;
+    }
+
+    let loaded = load_cards(
+        json_path,
+        &options.tcg_type,
+        &options.filter,
+        options.parse_cache,
+    )?;
+    loaded.report_exclusions();
+    let mut unified_cards = loaded.cards;
+    // Name directories before any subset is taken so collisions are judged on the whole file
+    let collisions = apply_dir_naming(&mut unified_cards, options.dir_naming);
+    if !collisions.is_empty() {
+        println!(
+            "{} cards share a directory name with an earlier card and were numbered: {}",
+            collisions.len(),
+            collisions.join(", ")
+        );
+    }
+    // Retries and repairs target cards in sets that already exist, so never skip those
+    if options.only_new_sets && options.only_ids.is_none() {
+        if source.has_sets() {
+            skip_downloaded_sets(&mut unified_cards, output_dir)?;
+        } else {
+            println!(
+                "{} cards have no set, ignoring --only-new-sets",
+                source.name()
+            );
+        }
+    }
+    if let Some(only_ids) = &options.only_ids {
+        unified_cards.retain(|card| only_ids.contains(&card.id));
+    }
+    if let Some(card_list) = &options.card_list {
+        card_list.retain(&mut unified_cards);
+    }
+    let total_available = unified_cards.len();
+
+    // Handle sort parameter before truncating so --amount picks a meaningful subset
+    let mut cards_to_process = unified_cards;
+    if options.stable_order {
+        // Sorting by id first also breaks ties deterministically for --sort below
+        sort_cards(&mut cards_to_process, CardSort::Id);
+    }
+    if let Some(sort) = options.sort {
+        sort_cards(&mut cards_to_process, sort);
+    }
+
+    if let Some(per_set) = options.sample_per_set {
+        if source.has_sets() {
+            sample_per_set(&mut cards_to_process, per_set);
+        } else {
+            println!(
+                "{} cards have no set, ignoring --sample-per-set",
+                source.name()
+            );
+        }
+    }
+
+    // Handle amount parameter
+    if let Some(amt) = options.amount.as_deref() {
+        if amt != "all" {
+            if let Ok(limit) = amt.parse::<usize>() {
+                cards_to_process.truncate(limit);
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Invalid amount value",
+                ));
+            }
+        }
+    }
+
+    if options.filter.include_backs {
+        match source.card_back() {
+            Some(card_back) => cards_to_process.push(card_back),
+            None => println!("{} has no card backs to download, skipping", source.name()),
+        }
+    }
+
+    let total_cards = cards_to_process.len();
+    println!(
+        "Found {} cards with images, downloading {} cards using {} threads",
+        total_available, total_cards, thread_count
+    );
+
+    let image_store = options
+        .image_store
+        .clone()
+        .unwrap_or_else(|| Arc::new(LocalStore::new(&images_dir)));
+
+    // Cards an interrupted run finished are skipped without checking the output
+    let (checkpointed, cards_to_process): (Vec<_>, Vec<_>) = match &options.checkpoint {
+        Some(checkpoint) => cards_to_process
+            .into_iter()
+            .partition(|card| checkpoint.contains(&card.id)),
+        None => (Vec::new(), cards_to_process),
+    };
+
+    // Batch check which cards already exist
+    let existing_cards =
+        batch_check_existing_cards(image_store.as_ref(), &cards_to_process, options).await?;
+
+    // Filter out cards that already exist
+    let (mut existing, mut cards_to_download): (Vec<_>, Vec<_>) = cards_to_process
+        .into_iter()
+        .partition(|card| *existing_cards.get(&card.id).unwrap_or(&false));
+    existing.extend(checkpointed);
+
+    if let Some(manifest) = &options.manifest {
+        for card in &existing {
+            manifest.record(ManifestRecord {
+                id: card.id.clone(),
+                image_url: card.image_url.clone(),
+                path: image_store.display(&options.card_image_paths(card)[0]),
+                status: "existing",
+            })?;
+        }
+    }
+
+    // Reserved for the whole file now, and refunded below for cards that end up
+    // failed, skipped or linked instead of downloaded
+    if let Some(remaining_total) = &options.remaining_total {
+        let remaining = remaining_total.load(Ordering::Relaxed);
+        if cards_to_download.len() > remaining {
+            println!(
+                "Reached the --max-total budget, downloading only {} of {} new cards",
+                remaining,
+                cards_to_download.len()
+            );
+            cards_to_download.truncate(remaining);
+        }
+        remaining_total.fetch_sub(cards_to_download.len(), Ordering::Relaxed);
+    }
+
+    let cards_to_download_count = cards_to_download.len();
+    let already_existed = existing.len();
+
+    println!("Skipping {} cards that already exist", already_existed);
+    println!("Downloading {} new cards", cards_to_download_count);
+
+    if cards_to_download.is_empty() {
+        return Ok(DownloadSummary {
+            skipped_existing: already_existed,
+            ..Default::default()
+        });
+    }
+
+    let pb = ProgressBar::new(cards_to_download_count as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+
+    let skipped_existing = Arc::new(AtomicUsize::new(already_existed));
+    let skipped_soon = Arc::new(AtomicUsize::new(0));
+    let bytes_downloaded = Arc::new(AtomicU64::new(0));
+    let cache_hits = Arc::new(AtomicUsize::new(0));
+    let mirror_hits = Arc::new(AtomicUsize::new(0));
+    let started_at = Instant::now();
+
+    let failed_downloads = Arc::new(AtomicUsize::new(0));
+
+    if let Some(progress) = &options.progress {
+        progress.track_downloads(
+            pb.clone(),
+            started_at,
+            ProgressCounters {
+                bytes_downloaded: bytes_downloaded.clone(),
+                failed: failed_downloads.clone(),
+                skipped_soon: skipped_soon.clone(),
+                cache_hits: cache_hits.clone(),
+                mirror_hits: mirror_hits.clone(),
+            },
+        );
+    }
+
+    let schedule_gate = options.schedule.clone().map(|schedule| {
+        let pb = pb.clone();
+        Arc::new(ScheduleGate::new(schedule, move |message| {
+            pb.println(message)
+        }))
+    });
+    let schedule_watch = schedule_gate.clone().map(|gate| {
+        tokio::spawn(async move {
+            gate.watch().await;
+        })
+    });
+
+    let watchdog = options.stall_timeout.map(|stall_timeout| {
+        tokio::spawn(watch_for_stalls(
+            pb.clone(),
+            stall_timeout,
+            bytes_downloaded.clone(),
+            started_at,
+        ))
+    });
+
+    let context = CardDownloadContext {
+        options,
+        source,
+        client,
+        pb: pb.clone(),
+        images_dir: images_dir.clone(),
+        reference_train: options
+            .reference_dir
+            .as_ref()
+            .map(|reference| reference.join("data/train")),
+        image_store,
+        host_limiter: options
+            .per_host
+            .map(|per_host| Arc::new(HostLimiter::new(per_host))),
+        host_throttle: options.limit_rate_per_host.then(|| {
+            let pb = pb.clone();
+            Arc::new(HostThrottle::new(move |message| pb.println(message)))
+        }),
+        memory_budget: options
+            .max_memory
+            .map(|max_memory| Arc::new(MemoryBudget::new(max_memory))),
+        started_at,
+        skipped_soon: skipped_soon.clone(),
+        bytes_downloaded: bytes_downloaded.clone(),
+        cache_hits: cache_hits.clone(),
+        mirror_hits: mirror_hits.clone(),
+        linked: AtomicUsize::new(0),
+        insecure_rejected: AtomicUsize::new(0),
+        https_upgraded: AtomicUsize::new(0),
+        auto_cropped: AtomicUsize::new(0),
+        previewed: AtomicUsize::new(0),
+    };
+
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(thread_count));
+    let limiter = options
+        .adaptive_concurrency
+        .then(|| Arc::new(AdaptiveLimiter::new(thread_count)));
+    let redirect_failures = Arc::new(AtomicUsize::new(0));
+    let failed_cards = Arc::new(Mutex::new(Vec::new()));
+    let timings = Arc::new(Mutex::new(HashMap::new()));
+
+    // Each batch is set up, downloaded and dropped before the next, so per-card
+    // state for the whole run is never held at once
+    let batch_size = options.batch_size.unwrap_or(cards_to_download_count).max(1);
+    let batch_count = cards_to_download_count.div_ceil(batch_size);
+    let mut remaining_cards = cards_to_download.into_iter();
+    for batch_number in 1..=batch_count {
+        let batch: Vec<UnifiedCard> = remaining_cards.by_ref().take(batch_size).collect();
+        if batch_count > 1 {
+            pb.println(format!(
+                "Batch {}/{}: {} cards",
+                batch_number,
+                batch_count,
+                batch.len()
+            ));
+        }
+
+        // Create every card directory in one parallel pass instead of once per download task
+        let card_dirs: HashSet<PathBuf> = batch
+            .iter()
+            .filter_map(|card| {
+                images_dir
+                    .join(options.card_image_path(card))
+                    .parent()
+                    .map(Path::to_path_buf)
+            })
+            .collect();
+        card_dirs.par_iter().for_each(|dir| {
+            if let Err(e) = fs::create_dir_all(dir) {
+                eprintln!("Failed to create card directory {}: {}", dir.display(), e);
+            }
+        });
+
+        let downloads = batch.into_iter().map(|card| {
+            let failure = FailedDownload {
+                id: card.id.clone(),
+                image_url: card.image_url.clone(),
+            };
+            (failure, download_card(card, &context))
+        });
+
+        futures::stream::iter(downloads)
+            .map(|(failure, download)| {
+                let semaphore = semaphore.clone();
+                let failed_downloads = failed_downloads.clone();
+                let redirect_failures = redirect_failures.clone();
+                let failed_cards = failed_cards.clone();
+                let limiter = limiter.clone();
+                let schedule_gate = schedule_gate.clone();
+                let timings = timings.clone();
+                async move {
+                    let _permit = semaphore.acquire().await.unwrap();
+                    let _slot = match &schedule_gate {
+                        Some(gate) => Some(gate.acquire().await),
+                        None => None,
+                    };
+                    // Timed from when the card gets a download slot, so queueing is excluded
+                    let card_started = Instant::now();
+                    let result = match &limiter {
+                        Some(limiter) => {
+                            limiter.acquire().await;
+                            let request_started = Instant::now();
+                            let result = download.await;
+                            limiter.release(request_started.elapsed(), result.is_ok());
+                            result
+                        }
+                        None => download.await,
+                    };
+                    if options.track_timings {
+                        if let Ok(mut timings) = timings.lock() {
+                            timings.insert(
+                                failure.id.clone(),
+                                CardTiming {
+                                    image_url: failure.image_url.clone(),
+                                    elapsed: card_started.elapsed(),
+                                },
+                            );
+                        }
+                    }
+                    if let (Ok(()), Some(checkpoint)) = (&result, &options.checkpoint) {
+                        if let Err(e) = checkpoint.record(&failure.id) {
+                            eprintln!("Failed to save checkpoint: {}", e);
+                        }
+                    }
+                    if let Err(e) = &result {
+                        failed_downloads.fetch_add(1, Ordering::Relaxed);
+                        if is_redirect_failure(e) {
+                            redirect_failures.fetch_add(1, Ordering::Relaxed);
+                        }
+                        if let Ok(mut failed_cards) = failed_cards.lock() {
+                            failed_cards.push(failure);
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(thread_count)
+            .for_each(|_| async {})
+            .await;
+    }
+
+    if let Some(checkpoint) = &options.checkpoint {
+        if let Err(e) = checkpoint.save() {
+            eprintln!("Failed to save checkpoint: {}", e);
+        }
+    }
+
+    if let Some(schedule_watch) = schedule_watch {
+        schedule_watch.abort();
+    }
+    if let Some(watchdog) = watchdog {
+        watchdog.abort();
+    }
+    if let Some(progress) = &options.progress {
+        progress.finish_downloads();
+    }
+    pb.finish_with_message(format!(
+        "Download complete! ({})",
+        format_throughput(
+            bytes_downloaded.load(Ordering::Relaxed),
+            started_at.elapsed()
+        )
+    ));
+
+    if let Some(limiter) = &limiter {
+        println!("Adaptive concurrency converged to {}", limiter.limit());
+    }
+
+    let cache_hits = cache_hits.load(Ordering::Relaxed);
+    if cache_hits > 0 {
+        println!("Restored {} images from the image cache", cache_hits);
+    }
+
+    let mirrored = mirror_hits.load(Ordering::Relaxed);
+    if mirrored > 0 {
+        println!("Downloaded {} images from the image mirror", mirrored);
+    }
+
+    let linked = context.linked.load(Ordering::Relaxed);
+    if options.reference_dir.is_some() {
+        println!(
+            "Hard-linked {} cards from the reference dataset, {} needed downloading",
+            linked,
+            cards_to_download_count - linked
+        );
+    }
+
+    let insecure_rejected = context.insecure_rejected.load(Ordering::Relaxed);
+    if insecure_rejected > 0 {
+        println!(
+            "Skipped {} image URLs that are not HTTPS (--require-https)",
+            insecure_rejected
+        );
+    }
+    let https_upgraded = context.https_upgraded.load(Ordering::Relaxed);
+    if https_upgraded > 0 {
+        println!("Upgraded {} image URLs from HTTP to HTTPS", https_upgraded);
+    }
+
+    let auto_cropped = context.auto_cropped.load(Ordering::Relaxed);
+    if options.processing.auto_crop {
+        println!(
+            "Auto-cropped {} images to the detected card boundary",
+            auto_cropped
+        );
+    }
+
+    let failed_downloads = failed_downloads.load(Ordering::Relaxed);
+    let redirect_failures = redirect_failures.load(Ordering::Relaxed);
+    if failed_downloads > 0 {
+        eprintln!("Warning: {} downloads failed", failed_downloads);
+        if redirect_failures > 0 {
+            eprintln!(
+                "  {} of them exceeded the redirect limit (see --max-redirects)",
+                redirect_failures
+            );
+        }
+    }
+
+    let final_skipped_existing = skipped_existing.load(Ordering::Relaxed);
+    let final_skipped_soon = skipped_soon.load(Ordering::Relaxed);
+    let mut failed = failed_cards
+        .lock()
+        .map(|mut failed_cards| std::mem::take(&mut *failed_cards))
+        .unwrap_or_default();
+    if options.stable_order {
+        failed.sort_by(|a, b| a.id.cmp(&b.id));
+    }
+
+    let downloaded =
+        cards_to_download_count - failed.len() - final_skipped_soon - insecure_rejected - linked;
+    if let Some(remaining_total) = &options.remaining_total {
+        remaining_total.fetch_add(cards_to_download_count - downloaded, Ordering::Relaxed);
+    }
+
+    Ok(DownloadSummary {
+        downloaded,
+        skipped_existing: final_skipped_existing,
+        skipped_soon: final_skipped_soon,
+        failed,
+        redirect_failures,
+        mirrored,
+        linked,
+        insecure_rejected,
+        https_upgraded,
+        auto_cropped,
+        timings: timings
+            .lock()
+            .map(|mut timings| std::mem::take(&mut *timings))
+            .unwrap_or_default(),
+    })
+}
+
+/// State shared by the card downloads of one `download_card_images` call
+struct CardDownloadContext<'a> {
+    options: &'a DownloadOptions,
+    source: &'static dyn TcgSource,
+    client: reqwest::Client,
+    pb: ProgressBar,
+    images_dir: PathBuf,
+    /// `data/train` of the --reference-dir dataset
+    reference_train: Option<PathBuf>,
+    image_store: Arc<dyn ImageStore>,
+    host_limiter: Option<Arc<HostLimiter>>,
+    host_throttle: Option<Arc<HostThrottle>>,
+    memory_budget: Option<Arc<MemoryBudget>>,
+    started_at: Instant,
+    skipped_soon: Arc<AtomicUsize>,
+    bytes_downloaded: Arc<AtomicU64>,
+    cache_hits: Arc<AtomicUsize>,
+    mirror_hits: Arc<AtomicUsize>,
+    linked: AtomicUsize,
+    insecure_rejected: AtomicUsize,
+    https_upgraded: AtomicUsize,
+    auto_cropped: AtomicUsize,
+    previewed: AtomicUsize,
+}
+
+/// Download, validate and process one card's image and record it in the manifest
+///
+/// The progress bar is advanced once whatever the outcome.
+async fn download_card(card: UnifiedCard, context: &CardDownloadContext<'_>) -> io::Result<()> {
+    let options = context.options;
+    let pb = &context.pb;
+    let relative_path = options.card_image_path(&card);
+    let final_path = context.images_dir.join(&relative_path);
+    // Every file written for the card, relative to data/train and on disk
+    let outputs: Vec<(PathBuf, PathBuf)> = options
+        .processing
+        .output_paths(&relative_path)
+        .into_iter()
+        .map(|(path, _, _)| {
+            let local = context.images_dir.join(&path);
+            (path, local)
+        })
+        .collect();
+    let temp_ext = context.source.temp_extension();
+    // Templated layouts and --temp-dir put many cards in one directory, so name
+    // temp files per card there
+    let temp_path = match (&options.temp_dir, &options.output_template) {
+        (Some(temp_dir), _) => temp_dir.join(format!(
+            "{}.temp.{}",
+            card.dir_name.as_deref().unwrap_or(&card.id),
+            temp_ext
+        )),
+        (None, Some(_)) => final_path.with_extension(format!("temp.{}", temp_ext)),
+        (None, None) => final_path.with_file_name(format!("temp.{}", temp_ext)),
+    };
+    let image_url = card.image_url.clone();
+
+    // Directories were created up front; recreate one that failed or was removed since
+    let card_dir = final_path.parent().unwrap();
+    if !card_dir.is_dir() {
+        if let Err(e) = fs::create_dir_all(card_dir) {
+            pb.inc(1);
+            return Err(io::Error::other(format!(
+                "Failed to create card directory: {}",
+                e
+            )));
+        }
+    }
+
+    // Reuse the reference dataset's processed images when they match
+    if let Some(reference_train) = &context.reference_train {
+        match link_from_reference(
+            reference_train,
+            &context.images_dir,
+            &relative_path,
+            &options.processing,
+        ) {
+            Ok(true) => {
+                for (relative, local) in &outputs {
+                    if let Err(e) = context.image_store.put(relative, local).await {
+                        pb.inc(1);
+                        return Err(e);
+                    }
+                }
+                if let Some(manifest) = &options.manifest {
+                    if let Err(e) = manifest.record(ManifestRecord {
+                        id: card.id,
+                        image_url,
+                        path: context.image_store.display(&outputs[0].0),
+                        status: "linked",
+                    }) {
+                        eprintln!("Failed to write manifest record: {}", e);
+                    }
+                }
+                context.linked.fetch_add(1, Ordering::Relaxed);
+                pb.inc(1);
+                return Ok(());
+            }
+            Ok(false) => {}
+            Err(e) => pb.println(format!(
+                "Failed to link {} from the reference dataset: {}",
+                card.id, e
+            )),
+        }
+    }
+
+    // Skip cards whose image is a placeholder, like MTG's "soon.jpg"
+    if context
+        .source
+        .placeholder_urls()
+        .iter()
+        .any(|placeholder| image_url.contains(placeholder))
+    {
+        context.skipped_soon.fetch_add(1, Ordering::Relaxed);
+        pb.inc(1);
+        return Ok(());
+    }
+
+    // Check the scheme before the URL is used for the cache, a download or the manifest
+    let image_url = match options.require_https {
+        Some(policy) => match enforce_https(&image_url, policy) {
+            Some((url, upgraded)) => {
+                if upgraded {
+                    context.https_upgraded.fetch_add(1, Ordering::Relaxed);
+                }
+                url
+            }
+            None => {
+                context.insecure_rejected.fetch_add(1, Ordering::Relaxed);
+                pb.inc(1);
+                return Ok(());
+            }
+        },
+        None => image_url,
+    };
+
+    // Reuse a cached copy of the source image when one exists
+    let restored = match &options.cache {
+        Some(cache) => cache.restore(&image_url, &temp_path).unwrap_or_else(|e| {
+            pb.println(format!("Failed to read image cache: {}", e));
+            false
+        }),
+        None => false,
+    };
+
+    let downloaded = if restored {
+        context.cache_hits.fetch_add(1, Ordering::Relaxed);
+        None
+    } else {
+        // Host slots are only held while a request is in flight
+        let fetch = |url: String| {
+            let client = &context.client;
+            let host_limiter = &context.host_limiter;
+            let host_throttle = context.host_throttle.as_deref();
+            async move {
+                let _host_permit = match host_limiter {
+                    Some(limiter) => limiter.acquire(&url).await,
+                    None => None,
+                };
+                fetch_image_bytes(client, &url, host_throttle).await
+            }
+        };
+        let bytes = match fetch(image_url.clone()).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let mirrored = match options
+                    .image_mirror
+                    .as_ref()
+                    .and_then(|mirror| mirror_url(&image_url, mirror))
+                {
+                    Some(mirrored) => fetch(mirrored).await,
+                    None => Err(e),
+                };
+                match mirrored {
+                    Ok(bytes) => {
+                        context.mirror_hits.fetch_add(1, Ordering::Relaxed);
+                        bytes
+                    }
+                    Err(e) => {
+                        pb.inc(1);
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        let total_bytes = context
+            .bytes_downloaded
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed)
+            + bytes.len() as u64;
+        pb.set_message(format_throughput(total_bytes, context.started_at.elapsed()));
+
+        if let Err(e) = fs::File::create(&temp_path).and_then(|mut file| file.write_all(&bytes)) {
+            if temp_path.exists() {
+                if let Err(cleanup_err) = fs::remove_file(&temp_path) {
+                    eprintln!("Failed to cleanup temp file: {}", cleanup_err);
+                }
+            }
+            pb.inc(1);
+            return Err(e);
+        }
+        Some(bytes)
+    };
+
+    // Hold a share of the memory budget while the image is decoded and processed;
+    // the download slot stays taken meanwhile, so both limits apply
+    let _memory = match &context.memory_budget {
+        Some(budget) => {
+            budget
+                .reserve(estimated_processing_bytes(&temp_path, &options.processing))
+                .await
+        }
+        None => None,
+    };
+
+    if let Err(e) = validate_image(&temp_path) {
+        if let Err(cleanup_err) = fs::remove_file(&temp_path) {
+            eprintln!("Failed to cleanup corrupted image file: {}", cleanup_err);
+        }
+        pb.inc(1);
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Corrupted image detected: {} - URL: {}", e, image_url),
+        ));
+    }
+
+    // Only cache images that decoded cleanly
+    if let (Some(cache), Some(bytes)) = (&options.cache, &downloaded) {
+        if let Err(e) = cache.store(&image_url, bytes) {
+            pb.println(format!("Failed to write image cache: {}", e));
+        }
+    }
+
+    let info = match process_image(&temp_path, &final_path, &options.processing) {
+        Ok(info) => info,
+        Err(e) => {
+            // Only try to cleanup temp file if it still exists (process_image failed)
+            if temp_path.exists() {
+                if let Err(cleanup_err) = fs::remove_file(&temp_path) {
+                    eprintln!("Failed to cleanup temp file: {}", cleanup_err);
+                }
+            }
+            pb.inc(1);
+            return Err(e);
+        }
+    };
+
+    if info.auto_cropped {
+        context.auto_cropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if context.previewed.fetch_add(1, Ordering::Relaxed) < options.preview {
+        pb.println(format!(
+            "Preview {}: {}x{}, {:.1} KB -> {}",
+            card.id,
+            info.width,
+            info.height,
+            info.file_size as f64 / 1024.0,
+            outputs[0].1.display()
+        ));
+    }
+
+    for (relative, local) in &outputs {
+        if let Err(e) = context.image_store.put(relative, local).await {
+            pb.inc(1);
+            return Err(e);
+        }
+    }
+
+    if let Some(manifest) = &options.manifest {
+        if let Err(e) = manifest.record(ManifestRecord {
+            id: card.id,
+            image_url,
+            path: context.image_store.display(&outputs[0].0),
+            status: if restored { "cached" } else { "downloaded" },
+        }) {
+            eprintln!("Failed to write manifest record: {}", e);
+        }
+    }
+
+    pb.inc(1);
+    Ok(())
+}
+
+/// Flag the progress bar as stalled when no download completes within `stall_timeout`
+///
+/// The warning is cleared, and the throughput message restored, as soon as the
+/// bar moves again. Runs until aborted.
+async fn watch_for_stalls(
+    pb: ProgressBar,
+    stall_timeout: Duration,
+    bytes_downloaded: Arc<AtomicU64>,
+    started_at: Instant,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut last_position = pb.position();
+    let mut last_progress = Instant::now();
+    let mut stalled = false;
+
+    loop {
+        interval.tick().await;
+        let position = pb.position();
+        if position != last_position {
+            last_position = position;
+            last_progress = Instant::now();
+            if stalled {
+                stalled = false;
+                pb.println("Downloads resumed");
+                pb.set_message(format_throughput(
+                    bytes_downloaded.load(Ordering::Relaxed),
+                    started_at.elapsed(),
+                ));
+            }
+        } else if !stalled && last_progress.elapsed() >= stall_timeout {
+            stalled = true;
+            pb.set_message("stalled — waiting on network");
+            pb.println(format!(
+                "Warning: no download completed in the last {}s, the network may be stalled",
+                stall_timeout.as_secs()
+            ));
+        }
+    }
+}
+
+/// Format download throughput for the progress bar message
+pub(crate) fn format_throughput(total_bytes: u64, elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    if seconds <= 0.0 {
+        return String::new();
+    }
+
+    format!("{:.2} MB/s", total_bytes as f64 / seconds / 1_000_000.0)
+}
+
+/// Batch check which cards already exist to avoid re-downloading
+pub async fn batch_check_existing_cards(
+    store: &dyn ImageStore,
+    cards: &[UnifiedCard],
+    options: &DownloadOptions,
+) -> io::Result<HashMap<String, bool>> {
+    // A card with several --sizes only counts as existing when every size is there
+    let paths: Vec<Vec<PathBuf>> = cards
+        .iter()
+        .map(|card| options.card_image_paths(card))
+        .collect();
+    let all_paths: Vec<PathBuf> = paths.iter().flatten().cloned().collect();
+    let existing = store.existing(&all_paths).await?;
+
+    Ok(cards
+        .iter()
+        .zip(&paths)
+        .map(|(card, paths)| {
+            let complete = paths.iter().all(|path| existing.contains(path));
+            (card.id.clone(), complete)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// A fresh directory under the system temp dir for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tcg-fetch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn processing(strip_metadata: bool) -> ImageProcessing {
+        ImageProcessing {
+            width: 32,
+            height: 44,
+            format: OutputFormat::Jpeg,
+            channels: Channels::Rgb,
+            max_dimension: None,
+            strip_metadata,
+            auto_contrast: None,
+            sharpen: None,
+            auto_crop: false,
+            sizes: Vec::new(),
+            convert_srgb: false,
+        }
+    }
+
+    /// A small JPEG with an EXIF segment spliced in after the start of image
+    fn jpeg_with_exif() -> Vec<u8> {
+        let img = RgbImage::from_fn(64, 88, |x, y| Rgb([(x * 4) as u8, (y * 2) as u8, 128]));
+        let mut encoded = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut encoded, ImageFormat::Jpeg)
+            .unwrap();
+        let encoded = encoded.into_inner();
+
+        let payload = b"Exif\0\0tcg-fetch test metadata";
+        let length = (payload.len() + 2) as u16;
+        let mut jpeg = encoded[..2].to_vec();
+        jpeg.extend_from_slice(&[0xFF, 0xE1]);
+        jpeg.extend_from_slice(&length.to_be_bytes());
+        jpeg.extend_from_slice(payload);
+        jpeg.extend_from_slice(&encoded[2..]);
+        jpeg
+    }
+
+    /// Markers of the segments before the start of scan
+    fn segment_markers(jpeg: &[u8]) -> Vec<u8> {
+        let mut markers = Vec::new();
+        let mut pos = 2;
+        while pos + 4 <= jpeg.len() && jpeg[pos] == 0xFF && jpeg[pos + 1] != 0xDA {
+            markers.push(jpeg[pos + 1]);
+            pos += 2 + u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]) as usize;
+        }
+        markers
+    }
+
+    fn is_metadata(marker: &u8) -> bool {
+        matches!(marker, 0xE0..=0xEF | 0xFE)
+    }
+
+    #[test]
+    fn parse_image_dimension_accepts_the_bounds() {
+        assert_eq!(parse_image_dimension("10"), Ok(MIN_IMAGE_DIMENSION));
+        assert_eq!(parse_image_dimension(" 500 "), Ok(500));
+        assert_eq!(parse_image_dimension("10000"), Ok(MAX_IMAGE_DIMENSION));
+    }
+
+    #[test]
+    fn parse_image_dimension_rejects_invalid_values() {
+        for value in [
+            "0",
+            "",
+            "   ",
+            "abc",
+            "12px",
+            "-5",
+            "9",
+            "10001",
+            "99999999999",
+        ] {
+            assert!(
+                parse_image_dimension(value).is_err(),
+                "'{}' should be rejected",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn strip_jpeg_metadata_removes_exif() {
+        let jpeg = jpeg_with_exif();
+        assert!(segment_markers(&jpeg).contains(&0xE1));
+
+        let stripped = strip_jpeg_metadata(&jpeg);
+        assert!(!segment_markers(&stripped).iter().any(is_metadata));
+        assert!(stripped.len() < jpeg.len());
+        image::load_from_memory(&stripped).unwrap();
+    }
+
+    #[test]
+    fn process_image_strips_metadata_only_when_asked() {
+        let dir = test_dir("strip-metadata");
+        let mut outputs = Vec::new();
+        for strip in [false, true] {
+            let source = dir.join(format!("source-{}.jpg", strip));
+            let target = dir.join(format!("target-{}.jpg", strip));
+            fs::write(&source, jpeg_with_exif()).unwrap();
+            process_image(&source, &target, &processing(strip)).unwrap();
+            outputs.push(fs::read(&target).unwrap());
+        }
+        let (kept, stripped) = (&outputs[0], &outputs[1]);
+
+        assert!(segment_markers(kept).iter().any(is_metadata));
+        assert!(!segment_markers(stripped).iter().any(is_metadata));
+        assert!(stripped.len() < kept.len());
+        assert_eq!(
+            image::load_from_memory(stripped).unwrap().dimensions(),
+            (32, 44)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn redirect_with_redirects_disabled_is_a_redirect_failure() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/card.png", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            stream
+                .write_all(
+                    b"HTTP/1.1 301 Moved Permanently\r\nLocation: /moved.png\r\nContent-Length: 0\r\n\r\n",
+                )
+                .unwrap();
+        });
+
+        let client = crate::utils::http::build_client(&crate::utils::http::HttpOptions {
+            max_redirects: Some(0),
+            ..Default::default()
+        })
+        .unwrap();
+        let error = fetch_image_bytes(&client, &url, None).await.unwrap_err();
+        assert!(is_redirect_failure(&error));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::utils::json_shape::describe;
+use serde_json::Value;
+use std::fmt;
+
+/// Why `*` is rejected; the list of cards is selected separately
+const WILDCARD_HINT: &str =
+    "wildcards are not supported, use --records-path to select the list of cards";
+
+/// One step of a [`JsonPath`]
+#[derive(Debug, Clone)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(key) if key.contains(['.', '[', ']', ' ']) => write!(f, "[\"{}\"]", key),
+            Self::Key(key) => write!(f, ".{}", key),
+            Self::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+/// A JSONPath-like expression selecting one value inside a JSON document
+///
+/// `$` is the document (or the record the path is applied to), followed by
+/// `.key`, `["key"]` and `[index]` steps, e.g. `$.images.large` or
+/// `$.editions[0].image`. The leading `$` may be left out. Wildcards and
+/// filters are not supported since every path selects a single value.
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+impl JsonPath {
+    /// Parse and validate a path expression
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let invalid = |problem: &str| format!("Invalid path '{}': {}", expression, problem);
+
+        let trimmed = expression.trim();
+        let mut rest = trimmed.strip_prefix('$').unwrap_or(trimmed);
+        let bare_key = format!(".{}", rest);
+        if !rest.is_empty() && !rest.starts_with(['.', '[']) {
+            // `name` is short for `$.name`
+            rest = &bare_key;
+        }
+
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                let key = &after_dot[..end];
+                if key.is_empty() {
+                    return Err(invalid("empty key after '.'"));
+                }
+                if key == "*" {
+                    return Err(invalid(WILDCARD_HINT));
+                }
+                steps.push(Step::Key(key.to_string()));
+                rest = &after_dot[end..];
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket
+                    .find(']')
+                    .ok_or_else(|| invalid("unclosed '['"))?;
+                let inner = after_bracket[..end].trim();
+                let quoted = ['"', '\'']
+                    .iter()
+                    .find_map(|&quote| inner.strip_prefix(quote)?.strip_suffix(quote));
+                steps.push(match quoted {
+                    Some(key) => Step::Key(key.to_string()),
+                    None if inner == "*" => return Err(invalid(WILDCARD_HINT)),
+                    None => Step::Index(inner.parse().map_err(|_| {
+                        invalid(&format!(
+                            "expected an index or a quoted key in brackets, found '{}'",
+                            inner
+                        ))
+                    })?),
+                });
+                rest = &after_bracket[end + 1..];
+            } else {
+                return Err(invalid(&format!("expected '.' or '[' before '{}'", rest)));
+            }
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// The value this path selects in `value`, or why nothing was found
+    pub fn resolve<'a>(&self, value: &'a Value) -> Result<&'a Value, String> {
+        let mut current = value;
+        for (depth, step) in self.steps.iter().enumerate() {
+            let next = match step {
+                Step::Key(key) => current.get(key),
+                Step::Index(index) => current.get(index),
+            };
+            current = next.ok_or_else(|| {
+                let location = Self {
+                    steps: self.steps[..depth].to_vec(),
+                };
+                let found = match current {
+                    Value::Object(object) if object.is_empty() => "an empty object".to_string(),
+                    Value::Object(object) => format!(
+                        "an object with keys: {}",
+                        object
+                            .keys()
+                            .take(10)
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    Value::Array(array) => format!("an array of {} items", array.len()),
+                    other => describe(other).to_string(),
+                };
+                format!(
+                    "'{}' stops matching at '{}': '{}' is {}",
+                    self, step, location, found
+                )
+            })?;
+        }
+        Ok(current)
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$")?;
+        for step in &self.steps {
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use serde_json::Value;
+use std::io;
+
+/// Expected layout of a JSON document fetched from a card API
+///
+/// Only the structure is checked: where the records live and which fields the
+/// first record has. That is enough to tell an API format change (or an error
+/// page served instead of data) apart from the odd malformed card, and gives a
+/// clearer message than serde's first field error.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonShape {
+    /// What the document is, for messages, e.g. "Scryfall bulk data index"
+    pub source: &'static str,
+    /// Object keys leading to the records; empty when the document itself holds them
+    pub records_at: &'static [&'static str],
+    /// Whether the records are an object keyed by id rather than an array
+    pub keyed_records: bool,
+    /// Fields every record is expected to have
+    pub record_fields: &'static [&'static str],
+}
+
+impl JsonShape {
+    /// Confirm `document` has this shape, explaining what looks wrong if not
+    pub fn check(&self, document: &Value) -> io::Result<()> {
+        let mut current = document;
+        let mut location = String::from("the top level");
+        for key in self.records_at {
+            current = match current.get(key) {
+                Some(value) => value,
+                None => {
+                    return Err(self.unexpected(
+                        document,
+                        format!(
+                            "expected an object with a '{}' field at {} but found {}",
+                            key,
+                            location,
+                            describe(current)
+                        ),
+                    ))
+                }
+            };
+            location = format!("'{}'", key);
+        }
+
+        let first = if self.keyed_records {
+            match current {
+                Value::Object(records) => records.values().next(),
+                other => {
+                    return Err(self.unexpected(
+                        document,
+                        format!(
+                            "expected an object at {} but found {}",
+                            location,
+                            describe(other)
+                        ),
+                    ))
+                }
+            }
+        } else {
+            match current {
+                Value::Array(records) => records.first(),
+                other => {
+                    return Err(self.unexpected(
+                        document,
+                        format!(
+                            "expected an array at {} but found {}",
+                            location,
+                            describe(other)
+                        ),
+                    ))
+                }
+            }
+        };
+
+        let Some(first) = first else {
+            return Ok(());
+        };
+        let Value::Object(record) = first else {
+            return Err(self.unexpected(
+                document,
+                format!(
+                    "expected records at {} to be objects but found {}",
+                    location,
+                    describe(first)
+                ),
+            ));
+        };
+        let missing: Vec<&str> = self
+            .record_fields
+            .iter()
+            .copied()
+            .filter(|field| !record.contains_key(*field))
+            .collect();
+        if !missing.is_empty() {
+            return Err(self.unexpected(
+                document,
+                format!(
+                    "records at {} are missing {} (found: {})",
+                    location,
+                    missing.join(", "),
+                    record
+                        .keys()
+                        .take(10)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn unexpected(&self, document: &Value, problem: String) -> io::Error {
+        // APIs tend to answer failures with a small object carrying the reason
+        let reason = ["details", "message", "error"]
+            .iter()
+            .find_map(|key| document.get(key).and_then(Value::as_str))
+            .map(|reason| format!(" The response says: {}.", reason))
+            .unwrap_or_default();
+
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} has an unexpected format: {}.{} The API may be down or may have changed its format; \
+                 try again later with --refresh-json, and if it keeps happening update tcg-fetch or \
+                 report it at https://github.com/acidtib/tcg-fetch/issues",
+                self.source, problem, reason
+            ),
+        )
+    }
+}
+
+/// Short description of a JSON value's type for error messages
+pub(crate) fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
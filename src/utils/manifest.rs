@@ -0,0 +1,111 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Output format for the dataset manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ManifestFormat {
+    /// A single JSON array written once all downloads finish
+    Json,
+    /// JSON Lines, one record per card written as each download completes
+    Jsonl,
+}
+
+/// Metadata record describing a single card in the dataset
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestRecord {
+    pub id: String,
+    pub image_url: String,
+    pub path: String,
+    pub status: &'static str,
+}
+
+enum ManifestSink {
+    Buffered(Vec<ManifestRecord>),
+    Jsonl(BufWriter<fs::File>),
+}
+
+/// Concurrency-safe manifest writer shared by all download tasks
+pub struct ManifestWriter {
+    path: PathBuf,
+    format: ManifestFormat,
+    sorted: bool,
+    sink: Mutex<ManifestSink>,
+}
+
+impl ManifestWriter {
+    /// Create a manifest at the given path, truncating any previous one
+    ///
+    /// With `sorted`, records are kept until `finish` and written ordered by card
+    /// id, so JSONL manifests are no longer streamed.
+    pub fn create(path: &Path, format: ManifestFormat, sorted: bool) -> io::Result<Self> {
+        let sink = match format {
+            ManifestFormat::Jsonl if !sorted => {
+                ManifestSink::Jsonl(BufWriter::new(fs::File::create(path)?))
+            }
+            _ => ManifestSink::Buffered(Vec::new()),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            sorted,
+            sink: Mutex::new(sink),
+        })
+    }
+
+    /// Record a card; streamed JSONL records are written immediately
+    pub fn record(&self, record: ManifestRecord) -> io::Result<()> {
+        let mut sink = self
+            .sink
+            .lock()
+            .map_err(|_| io::Error::other("Manifest writer lock poisoned"))?;
+
+        match &mut *sink {
+            ManifestSink::Buffered(records) => records.push(record),
+            ManifestSink::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, &record)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flush any buffered records to disk
+    pub fn finish(&self) -> io::Result<()> {
+        let mut sink = self
+            .sink
+            .lock()
+            .map_err(|_| io::Error::other("Manifest writer lock poisoned"))?;
+
+        match &mut *sink {
+            ManifestSink::Buffered(records) => {
+                if self.sorted {
+                    records.sort_by(|a, b| a.id.cmp(&b.id));
+                }
+                match self.format {
+                    ManifestFormat::Json => {
+                        let json_data = serde_json::to_string_pretty(records)?;
+                        fs::write(&self.path, json_data)?;
+                    }
+                    ManifestFormat::Jsonl => {
+                        let mut writer = BufWriter::new(fs::File::create(&self.path)?);
+                        for record in records.iter() {
+                            serde_json::to_writer(&mut writer, record)?;
+                            writer.write_all(b"\n")?;
+                        }
+                        writer.flush()?;
+                    }
+                }
+            }
+            ManifestSink::Jsonl(writer) => writer.flush()?,
+        }
+
+        println!("Manifest written to: {}", self.path.display());
+        Ok(())
+    }
+}
@@ -1,12 +1,40 @@
 //! Utility modules for TCG Fetch
 //!
 //! This module contains various utility functions organized by functionality:
+//! - `cache`: Content-addressable image cache
+//! - `card_list`: Card lists for targeted fetches
+//! - `concurrency`: Adaptive download concurrency
+//! - `config`: Config file support
 //! - `files`: File operations and directory management
 //! - `images`: Image processing and downloading
+//! - `hash_index`: Perceptual hash index output
 //! - `http`: HTTP client utilities
+//! - `json_path`: Path expressions into card JSON
+//! - `json_shape`: Structural checks of fetched card JSON
+//! - `manifest`: Dataset manifest output
+//! - `parse_cache`: Parsed card lists reused across runs
+//! - `progress`: Progress reports on SIGUSR1
+//! - `schedule`: Time windows for downloads
+//! - `state`: Run state persisted between invocations
+//! - `storage`: Local and object-storage destinations for images
+//! - `template`: Output path templates
 
+pub mod cache;
+pub mod card_list;
+pub mod concurrency;
+pub mod config;
 pub mod files;
+pub mod hash_index;
 pub mod http;
 pub mod images;
+pub mod json_path;
+pub mod json_shape;
+pub mod manifest;
+pub mod parse_cache;
+pub mod progress;
+pub mod schedule;
+pub mod state;
+pub mod storage;
+pub mod template;
 
 // Re-export commonly used functions for convenience
@@ -0,0 +1,93 @@
+use crate::tcg::TcgType;
+use crate::utils::images::{load_unified_cards, CardFilter, LoadedCards};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Parsed cards saved next to their source JSON, valid while the source is unchanged
+#[derive(Serialize, Deserialize)]
+struct ParseCache {
+    /// Version of tcg-fetch that wrote the cache, since parsing rules change between releases
+    version: String,
+    source_len: u64,
+    source_modified: u64,
+    filter: CardFilter,
+    loaded: LoadedCards,
+}
+
+/// Location of the parse cache for a source JSON file, e.g. `mtg_cards.parsed.json`
+fn cache_path(json_path: &Path) -> PathBuf {
+    json_path.with_extension("parsed.json")
+}
+
+/// Size and modification time (nanoseconds since the epoch) identifying a source file
+fn source_stamp(json_path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(json_path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64);
+    Ok((metadata.len(), modified))
+}
+
+/// Load the cards in a bulk JSON file, reusing a previous parse when `use_cache` is set
+///
+/// The cache is keyed on the source file's size and modification time and on the
+/// filter, so it is rebuilt whenever the card JSON is refreshed or the filter
+/// flags change.
+pub fn load_cards(
+    json_path: &str,
+    tcg_type: &TcgType,
+    filter: &CardFilter,
+    use_cache: bool,
+) -> io::Result<LoadedCards> {
+    if !use_cache {
+        return load_unified_cards(json_path, tcg_type, filter);
+    }
+
+    let source = Path::new(json_path);
+    let cache_file = cache_path(source);
+    let (source_len, source_modified) = source_stamp(source)?;
+
+    let cached = fs::read(&cache_file)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<ParseCache>(&bytes).ok())
+        .filter(|cache| {
+            cache.version == env!("CARGO_PKG_VERSION")
+                && cache.source_len == source_len
+                && cache.source_modified == source_modified
+                && cache.filter == *filter
+        });
+    if let Some(cache) = cached {
+        println!("Reusing parsed cards from {}", cache_file.display());
+        return Ok(cache.loaded);
+    }
+
+    let loaded = load_unified_cards(json_path, tcg_type, filter)?;
+    let cache = ParseCache {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        source_len,
+        source_modified,
+        filter: *filter,
+        loaded,
+    };
+
+    // A cache that cannot be written only costs the next run a full parse
+    let partial = cache_file.with_extension("partial");
+    let written = serde_json::to_vec(&cache)
+        .map_err(io::Error::other)
+        .and_then(|bytes| fs::write(&partial, bytes))
+        .and_then(|_| fs::rename(&partial, &cache_file));
+    if let Err(e) = written {
+        eprintln!(
+            "Failed to write parse cache {}: {}",
+            cache_file.display(),
+            e
+        );
+        let _ = fs::remove_file(&partial);
+    }
+
+    Ok(cache.loaded)
+}
@@ -0,0 +1,159 @@
+use crate::utils::images::format_throughput;
+use indicatif::ProgressBar;
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::Instant;
+
+/// Counters shared by the download tasks, read when reporting progress
+pub struct ProgressCounters {
+    pub bytes_downloaded: Arc<AtomicU64>,
+    pub failed: Arc<AtomicUsize>,
+    pub skipped_soon: Arc<AtomicUsize>,
+    pub cache_hits: Arc<AtomicUsize>,
+    pub mirror_hits: Arc<AtomicUsize>,
+}
+
+/// The image downloads of the file being processed
+struct Downloads {
+    pb: ProgressBar,
+    started_at: Instant,
+    counters: ProgressCounters,
+}
+
+struct ReportState {
+    phase: String,
+    downloads: Option<Downloads>,
+}
+
+/// Prints what a fetch is doing to stderr whenever the process receives SIGUSR1
+///
+/// Lets users check on a detached run with `kill -USR1 <pid>` without
+/// interrupting it. The signal is handled from creation until the reporter is
+/// dropped, so one sent while card JSON is still being fetched prints the
+/// current step instead of terminating the process. Must be created within a
+/// Tokio runtime.
+pub struct ProgressReporter {
+    state: Arc<Mutex<ReportState>>,
+    #[cfg(unix)]
+    listener: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl ProgressReporter {
+    pub fn new() -> Self {
+        let state = Arc::new(Mutex::new(ReportState {
+            phase: String::from("Starting"),
+            downloads: None,
+        }));
+
+        #[cfg(unix)]
+        let listener = {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            // Registered here rather than in the task so no signal is missed
+            // before the task first runs
+            match signal(SignalKind::user_defined1()) {
+                Ok(mut signals) => {
+                    let state = state.clone();
+                    let started_at = Instant::now();
+                    Some(tokio::spawn(async move {
+                        while signals.recv().await.is_some() {
+                            report(&state.lock().unwrap(), started_at);
+                        }
+                    }))
+                }
+                Err(e) => {
+                    eprintln!("Failed to listen for SIGUSR1: {}", e);
+                    None
+                }
+            }
+        };
+
+        Self {
+            state,
+            #[cfg(unix)]
+            listener,
+        }
+    }
+
+    /// Describe the step the fetch is on
+    pub fn set_phase(&self, phase: impl Into<String>) {
+        self.state.lock().unwrap().phase = phase.into();
+    }
+
+    /// Include the card counts and download counters of `pb` in reports
+    pub fn track_downloads(
+        &self,
+        pb: ProgressBar,
+        started_at: Instant,
+        counters: ProgressCounters,
+    ) {
+        self.state.lock().unwrap().downloads = Some(Downloads {
+            pb,
+            started_at,
+            counters,
+        });
+    }
+
+    /// Stop reporting the downloads passed to `track_downloads`
+    pub fn finish_downloads(&self) {
+        self.state.lock().unwrap().downloads = None;
+    }
+}
+
+impl Default for ProgressReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(listener) = &self.listener {
+            listener.abort();
+        }
+    }
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn report(state: &ReportState, started_at: Instant) {
+    let Some(downloads) = &state.downloads else {
+        eprintln!(
+            "Progress: {} after {}s",
+            state.phase,
+            started_at.elapsed().as_secs()
+        );
+        return;
+    };
+
+    let pb = &downloads.pb;
+    let counters = &downloads.counters;
+    let position = pb.position();
+    let length = pb.length().unwrap_or(0);
+    let elapsed = downloads.started_at.elapsed();
+    let bytes = counters.bytes_downloaded.load(Ordering::Relaxed);
+    let report = format!(
+        "Progress: {}, {}/{} cards ({:.1}%) after {}s\n  \
+         Downloaded {:.1} MB ({})\n  \
+         Failed: {}, soon.jpg skipped: {}, from cache: {}, from mirror: {}",
+        state.phase,
+        position,
+        length,
+        if length > 0 {
+            position as f64 / length as f64 * 100.0
+        } else {
+            0.0
+        },
+        elapsed.as_secs(),
+        bytes as f64 / 1_000_000.0,
+        format_throughput(bytes, elapsed),
+        counters.failed.load(Ordering::Relaxed),
+        counters.skipped_soon.load(Ordering::Relaxed),
+        counters.cache_hits.load(Ordering::Relaxed),
+        counters.mirror_hits.load(Ordering::Relaxed),
+    );
+    // Suspend the bar so the report is not drawn over
+    pb.suspend(|| eprintln!("{}", report));
+}
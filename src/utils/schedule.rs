@@ -0,0 +1,203 @@
+use chrono::{Local, NaiveTime};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// How often the schedule is checked for a window change
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What downloads may do during part of the day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    /// Run at the configured concurrency
+    Full,
+    /// Run at most this many downloads at once
+    Limit(usize),
+    /// Start no new downloads
+    Pause,
+}
+
+impl fmt::Display for WindowMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "downloading at full speed"),
+            Self::Limit(limit) => write!(f, "downloading at most {} at a time", limit),
+            Self::Pause => write!(f, "paused until the next download window"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+    mode: WindowMode,
+}
+
+impl Window {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else if self.start > self.end {
+            // Wraps past midnight
+            time >= self.start || time < self.end
+        } else {
+            true
+        }
+    }
+}
+
+/// Daily time windows in which downloads run, in local time
+///
+/// Written as comma-separated `HH:MM-HH:MM[=MODE]` windows where the mode is
+/// `full` (the default), `pause` or a number of concurrent downloads, e.g.
+/// `22:00-07:00,07:00-09:00=2`. The first window containing the current time
+/// applies; outside every window downloads are paused.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+impl Schedule {
+    /// Parse a `--schedule` value
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let windows = value
+            .split(',')
+            .map(str::trim)
+            .filter(|window| !window.is_empty())
+            .map(Self::parse_window)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if windows.is_empty() {
+            return Err("Schedule has no windows, expected e.g. 22:00-07:00".to_string());
+        }
+        Ok(Self { windows })
+    }
+
+    fn parse_window(window: &str) -> Result<Window, String> {
+        let (times, mode) = window.split_once('=').unwrap_or((window, "full"));
+        let (start, end) = times.split_once('-').ok_or_else(|| {
+            format!(
+                "Invalid schedule window '{}', expected HH:MM-HH:MM[=full|pause|N]",
+                window
+            )
+        })?;
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M")
+                .map_err(|_| format!("Invalid time '{}' in schedule, expected HH:MM", value))
+        };
+        let mode = match mode.trim() {
+            "full" => WindowMode::Full,
+            "pause" => WindowMode::Pause,
+            limit => match limit.parse::<usize>() {
+                Ok(limit) if limit > 0 => WindowMode::Limit(limit),
+                _ => {
+                    return Err(format!(
+                        "Invalid schedule mode '{}', expected full, pause or a number of downloads",
+                        limit
+                    ))
+                }
+            },
+        };
+
+        Ok(Window {
+            start: time(start)?,
+            end: time(end)?,
+            mode,
+        })
+    }
+
+    /// What downloads may do at a time of day
+    pub fn mode_at(&self, time: NaiveTime) -> WindowMode {
+        self.windows
+            .iter()
+            .find(|window| window.contains(time))
+            .map_or(WindowMode::Pause, |window| window.mode)
+    }
+}
+
+struct GateState {
+    mode: WindowMode,
+    active: usize,
+}
+
+/// Holds downloads back according to a [`Schedule`]
+///
+/// Downloads already running when a window closes finish; only new ones wait.
+/// [`ScheduleGate::watch`] must run alongside to follow window changes.
+pub struct ScheduleGate {
+    schedule: Schedule,
+    state: Mutex<GateState>,
+    changed: Notify,
+    log: Box<dyn Fn(String) + Send + Sync>,
+}
+
+impl ScheduleGate {
+    /// Create a gate in the current window, reporting window changes through `log`
+    pub fn new(schedule: Schedule, log: impl Fn(String) + Send + Sync + 'static) -> Self {
+        let mode = schedule.mode_at(Local::now().time());
+        if mode != WindowMode::Full {
+            log(format!("Schedule: {}", mode));
+        }
+
+        Self {
+            schedule,
+            state: Mutex::new(GateState { mode, active: 0 }),
+            changed: Notify::new(),
+            log: Box::new(log),
+        }
+    }
+
+    /// Wait until the current window allows another download
+    pub async fn acquire(&self) -> ScheduleSlot<'_> {
+        loop {
+            // Registered before checking so a change in between is not missed
+            let changed = self.changed.notified();
+            if let Ok(mut state) = self.state.lock() {
+                let allowed = match state.mode {
+                    WindowMode::Full => true,
+                    WindowMode::Limit(limit) => state.active < limit,
+                    WindowMode::Pause => false,
+                };
+                if allowed {
+                    state.active += 1;
+                    return ScheduleSlot { gate: self };
+                }
+            }
+            changed.await;
+        }
+    }
+
+    /// Follow the schedule, logging and applying window changes; runs until aborted
+    pub async fn watch(&self) {
+        let mut interval = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mode = self.schedule.mode_at(Local::now().time());
+            let Ok(mut state) = self.state.lock() else {
+                return;
+            };
+            if state.mode != mode {
+                state.mode = mode;
+                drop(state);
+                (self.log)(format!("Schedule: {}", mode));
+                self.changed.notify_waiters();
+            }
+        }
+    }
+}
+
+/// A running download counted against the schedule, released on drop
+pub struct ScheduleSlot<'a> {
+    gate: &'a ScheduleGate,
+}
+
+impl Drop for ScheduleSlot<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.gate.state.lock() {
+            state.active -= 1;
+        }
+        self.gate.changed.notify_waiters();
+    }
+}
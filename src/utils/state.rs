@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// A card whose image failed to download during a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedDownload {
+    pub id: String,
+    pub image_url: String,
+}
+
+/// Location of the failed-download state file for a dataset
+fn failed_state_path(base_path: &str) -> PathBuf {
+    Path::new(base_path).join("failed_downloads.json")
+}
+
+/// Load the failed downloads recorded by the previous run, if any
+pub fn load_failed_state(base_path: &str) -> io::Result<Option<Vec<FailedDownload>>> {
+    let state_path = failed_state_path(base_path);
+    if !state_path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&state_path)?;
+    let failed = serde_json::from_str(&content)
+        .map_err(|e| io::Error::other(format!("Failed to parse state file: {}", e)))?;
+    Ok(Some(failed))
+}
+
+/// Persist the failed downloads of this run, clearing the state when none failed
+pub fn save_failed_state(base_path: &str, failed: &[FailedDownload]) -> io::Result<()> {
+    let state_path = failed_state_path(base_path);
+
+    if failed.is_empty() {
+        if state_path.exists() {
+            fs::remove_file(&state_path)?;
+        }
+        return Ok(());
+    }
+
+    let json_data = serde_json::to_string_pretty(failed)?;
+    fs::write(&state_path, json_data)?;
+    println!(
+        "Recorded {} failed downloads in: {}",
+        failed.len(),
+        state_path.display()
+    );
+    Ok(())
+}
+
+/// Add the failed downloads of this run to those already recorded
+///
+/// Used when the run did not finish every file: cards it never reached keep
+/// their earlier failure, and the state file is kept even if nothing new failed.
+pub fn merge_failed_state(base_path: &str, failed: &[FailedDownload]) -> io::Result<()> {
+    let mut merged = load_failed_state(base_path)?.unwrap_or_default();
+    let ids: HashSet<&str> = failed.iter().map(|failure| failure.id.as_str()).collect();
+    merged.retain(|failure| !ids.contains(failure.id.as_str()));
+    merged.extend(failed.iter().cloned());
+    save_failed_state(base_path, &merged)
+}
+
+/// Location of the download checkpoint for a dataset
+fn checkpoint_path(base_path: &str) -> PathBuf {
+    Path::new(base_path).join("download_checkpoint.txt")
+}
+
+#[derive(Debug)]
+struct CheckpointState {
+    completed: HashSet<String>,
+    /// Recorded since the last save, in the order they finished
+    unsaved: Vec<String>,
+}
+
+/// Ids of the cards a run has finished, saved every `interval` cards
+///
+/// After a crash the next run skips these cards without checking the output for
+/// their images. Ids are appended one per line, so each save only writes the
+/// cards finished since the last one. The file is removed when a run completes,
+/// from then on the images on disk show what is done.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    interval: usize,
+    state: Mutex<CheckpointState>,
+    /// Held while appending, so the lines of concurrent saves don't interleave
+    file: Mutex<()>,
+}
+
+impl Checkpoint {
+    /// Open the checkpoint of a dataset, loading what an interrupted run saved
+    pub fn open(base_path: &str, interval: usize) -> io::Result<Self> {
+        let path = checkpoint_path(base_path);
+        let completed = if path.exists() {
+            let content = fs::read_to_string(&path)?;
+            // A crash mid-save can leave the last line unfinished, so drop it
+            let complete = content.rfind('\n').map_or("", |end| &content[..end]);
+            complete
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        Ok(Self {
+            path,
+            interval: interval.max(1),
+            state: Mutex::new(CheckpointState {
+                completed,
+                unsaved: Vec::new(),
+            }),
+            file: Mutex::new(()),
+        })
+    }
+
+    /// Number of cards recorded as finished
+    pub fn completed_count(&self) -> usize {
+        self.state.lock().map_or(0, |state| state.completed.len())
+    }
+
+    /// Whether a card was finished by this or an interrupted run
+    pub fn contains(&self, id: &str) -> bool {
+        self.state
+            .lock()
+            .is_ok_and(|state| state.completed.contains(id))
+    }
+
+    /// Record a finished card, saving the checkpoint every `interval` cards
+    pub fn record(&self, id: &str) -> io::Result<()> {
+        let due = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if !state.completed.insert(id.to_string()) {
+                return Ok(());
+            }
+            state.unsaved.push(id.to_string());
+            if state.unsaved.len() < self.interval {
+                return Ok(());
+            }
+            std::mem::take(&mut state.unsaved)
+        };
+        self.append(&due)
+    }
+
+    /// Save any cards recorded since the last save
+    pub fn save(&self) -> io::Result<()> {
+        let due = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::take(&mut state.unsaved)
+        };
+        if due.is_empty() {
+            return Ok(());
+        }
+        self.append(&due)
+    }
+
+    /// Append ids to the checkpoint file, written outside the state lock
+    fn append(&self, ids: &[String]) -> io::Result<()> {
+        let mut lines = String::new();
+        for id in ids {
+            lines.push_str(id);
+            lines.push('\n');
+        }
+
+        let _file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())
+    }
+
+    /// Delete the checkpoint once the run is complete
+    pub fn remove(&self) -> io::Result<()> {
+        let _file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
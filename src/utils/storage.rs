@@ -0,0 +1,191 @@
+use futures::future::BoxFuture;
+use futures::stream::TryStreamExt;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Where processed card images end up
+///
+/// Images are always processed on local disk first; `put` then moves the file
+/// written for a card into the store. Paths are relative to `data/train`.
+pub trait ImageStore: fmt::Debug + Send + Sync {
+    /// Which of `relative` are already stored
+    fn existing<'a>(
+        &'a self,
+        relative: &'a [PathBuf],
+    ) -> BoxFuture<'a, io::Result<HashSet<PathBuf>>>;
+
+    /// Store the processed image at `local` as `relative`
+    fn put<'a>(&'a self, relative: &'a Path, local: &'a Path) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Location of a stored image, for manifests and messages
+    fn display(&self, relative: &Path) -> String;
+}
+
+/// Images kept where they were processed, under `<path>/data/train`
+#[derive(Debug, Clone)]
+pub struct LocalStore {
+    train_dir: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(train_dir: &Path) -> Self {
+        Self {
+            train_dir: train_dir.to_path_buf(),
+        }
+    }
+}
+
+impl ImageStore for LocalStore {
+    fn existing<'a>(
+        &'a self,
+        relative: &'a [PathBuf],
+    ) -> BoxFuture<'a, io::Result<HashSet<PathBuf>>> {
+        Box::pin(async move {
+            Ok(relative
+                .par_iter()
+                .filter(|path| self.train_dir.join(path).exists())
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn put<'a>(&'a self, relative: &'a Path, local: &'a Path) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let target = self.train_dir.join(relative);
+            if local != target {
+                fs::rename(local, target)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn display(&self, relative: &Path) -> String {
+        self.train_dir.join(relative).display().to_string()
+    }
+}
+
+/// Images uploaded to an S3 (or S3-compatible) bucket
+///
+/// Credentials, region and endpoint come from the usual `AWS_*` environment
+/// variables, e.g. `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_REGION`
+/// and `AWS_ENDPOINT` for S3-compatible services.
+#[derive(Debug)]
+pub struct S3Store {
+    store: AmazonS3,
+    bucket: String,
+    /// Key prefix of `data/train`, without a trailing slash
+    prefix: String,
+}
+
+impl S3Store {
+    /// Connect to the bucket of an `s3://bucket/prefix` URL
+    pub fn new(url: &str) -> io::Result<Self> {
+        let (bucket, prefix) = parse_s3_url(url).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid S3 URL '{}', expected s3://bucket/prefix", url),
+            )
+        })?;
+
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(&bucket)
+            .build()
+            .map_err(|e| io::Error::other(format!("Failed to configure S3: {}", e)))?;
+
+        let prefix = match prefix.trim_matches('/') {
+            "" => "data/train".to_string(),
+            prefix => format!("{}/data/train", prefix),
+        };
+        Ok(Self {
+            store,
+            bucket,
+            prefix,
+        })
+    }
+
+    /// Object key of an image, always using `/` separators
+    fn key(&self, relative: &Path) -> String {
+        let mut key = self.prefix.clone();
+        for component in relative.components() {
+            if let Component::Normal(part) = component {
+                key.push('/');
+                key.push_str(&part.to_string_lossy());
+            }
+        }
+        key
+    }
+}
+
+impl ImageStore for S3Store {
+    fn existing<'a>(
+        &'a self,
+        relative: &'a [PathBuf],
+    ) -> BoxFuture<'a, io::Result<HashSet<PathBuf>>> {
+        Box::pin(async move {
+            // One listing of the prefix is far cheaper than a HEAD request per card
+            let prefix = ObjectPath::from(self.prefix.as_str());
+            let stored: HashSet<String> = self
+                .store
+                .list(Some(&prefix))
+                .map_ok(|object| object.location.to_string())
+                .try_collect()
+                .await
+                .map_err(|e| {
+                    io::Error::other(format!(
+                        "Failed to list s3://{}/{}: {}",
+                        self.bucket, self.prefix, e
+                    ))
+                })?;
+
+            Ok(relative
+                .iter()
+                .filter(|path| stored.contains(&self.key(path)))
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn put<'a>(&'a self, relative: &'a Path, local: &'a Path) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let bytes = tokio::fs::read(local).await?;
+            let key = self.key(relative);
+            self.store
+                .put(&ObjectPath::from(key.as_str()), PutPayload::from(bytes))
+                .await
+                .map_err(|e| {
+                    io::Error::other(format!(
+                        "Failed to upload s3://{}/{}: {}",
+                        self.bucket, key, e
+                    ))
+                })?;
+
+            // The local copy was only staged for the upload
+            tokio::fs::remove_file(local).await?;
+            if let Some(dir) = local.parent() {
+                let _ = tokio::fs::remove_dir(dir).await;
+            }
+            Ok(())
+        })
+    }
+
+    fn display(&self, relative: &Path) -> String {
+        format!("s3://{}/{}", self.bucket, self.key(relative))
+    }
+}
+
+/// Split an `s3://bucket/prefix` URL into its bucket and (possibly empty) prefix
+pub fn parse_s3_url(url: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("s3://")?;
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        return None;
+    }
+    Some((bucket.to_string(), prefix.to_string()))
+}
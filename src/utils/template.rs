@@ -0,0 +1,151 @@
+use crate::tcg::UnifiedCard;
+use std::path::PathBuf;
+
+/// Card metadata that can appear in an output path template
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Id,
+    Name,
+    Set,
+    Rarity,
+}
+
+/// A placeholder or literal piece of one path component
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// Extensions stripped from the rendered file name before the output format's is added
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
+
+/// Validated template for where each card's image is written under `data/train`
+///
+/// Placeholders are `{id}`, `{name}`, `{set}` and `{rarity}`; `/` separates
+/// directories, e.g. `{set}/{rarity}/{id}`. The output format's extension is
+/// always used for the file name.
+#[derive(Debug, Clone)]
+pub struct OutputTemplate {
+    components: Vec<Vec<Segment>>,
+}
+
+impl OutputTemplate {
+    /// Parse and validate an output path template
+    pub fn parse(template: &str) -> Result<Self, String> {
+        if template.starts_with('/') || template.contains('\\') {
+            return Err(format!(
+                "Output template must be a relative path using '/' separators: {}",
+                template
+            ));
+        }
+        if !template.contains("{id}") {
+            return Err(format!(
+                "Output template must contain {{id}} so every card gets its own file: {}",
+                template
+            ));
+        }
+
+        let components = template
+            .split('/')
+            .map(|component| Self::parse_component(template, component))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { components })
+    }
+
+    fn parse_component(template: &str, component: &str) -> Result<Vec<Segment>, String> {
+        if component.is_empty() || component == "." || component == ".." {
+            return Err(format!(
+                "Output template has an empty or relative path component: {}",
+                template
+            ));
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = component;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed placeholder in output template: {}", template))?
+                + start;
+            segments.push(Segment::Field(match &rest[start + 1..end] {
+                "id" => Field::Id,
+                "name" => Field::Name,
+                "set" => Field::Set,
+                "rarity" => Field::Rarity,
+                other => {
+                    return Err(format!(
+                        "Unknown placeholder {{{}}} in output template, expected id, name, set or rarity",
+                        other
+                    ))
+                }
+            }));
+            rest = &rest[end + 1..];
+        }
+        if rest.contains('}') {
+            return Err(format!("Unmatched '}}' in output template: {}", template));
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        Ok(segments)
+    }
+
+    /// Render the image path for a card, relative to `data/train`
+    pub fn render(&self, card: &UnifiedCard, extension: &str) -> PathBuf {
+        let mut path = PathBuf::new();
+        let last = self.components.len() - 1;
+
+        for (index, segments) in self.components.iter().enumerate() {
+            let mut component: String = segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Literal(text) => text.clone(),
+                    Segment::Field(field) => sanitize_component(match field {
+                        Field::Id => Some(card.id.as_str()),
+                        Field::Name => card.name.as_deref(),
+                        Field::Set => card.set.as_deref(),
+                        Field::Rarity => card.rarity.as_deref(),
+                    }),
+                })
+                .collect();
+
+            if index == last {
+                if let Some((stem, ext)) = component.rsplit_once('.') {
+                    if IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
+                        component = stem.to_string();
+                    }
+                }
+                component = format!("{}.{}", component, extension);
+            }
+            path.push(component);
+        }
+
+        path
+    }
+}
+
+/// Make a metadata value safe to use as (part of) a single path component
+pub(crate) fn sanitize_component(value: Option<&str>) -> String {
+    let sanitized: String = value
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim().trim_matches('.');
+
+    if sanitized.is_empty() {
+        "unknown".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}